@@ -0,0 +1,156 @@
+//! Fixes the program can apply by itself, named by `fix action` in the yaml file.
+//!
+//! The yaml file only holds the names, the code behind them is registered
+//! with [`add_fix_action`] when the program starts.
+
+use crate::json::string;
+use crate::{Phrases, UserPanic};
+use std::error::Error;
+use std::sync::{Arc, RwLock};
+
+type ActionFn = dyn Fn() -> Result<(), Box<dyn Error + Send + Sync>> + Send + Sync;
+
+struct FixAction {
+    name: String,
+    description: String,
+    run: Arc<ActionFn>,
+}
+
+static ACTIONS: RwLock<Vec<FixAction>> = RwLock::new(Vec::new());
+
+/// Whether the panic hook runs the fix actions of a [`UserPanic`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FixActionMode {
+    /// The actions are never run
+    #[default]
+    Never,
+    /// The user is asked before running each action, nothing is run when
+    /// stdin is not a terminal
+    Ask,
+    /// Every action is run without asking, for programs running unattended
+    Always,
+}
+
+/// Registers the code run for the `fix action` called `name`, replacing the
+/// one registered before
+///
+/// The description is shown when asking the user. Actions run inside the
+/// panic hook, so they must not panic themselves.
+///
+/// ```
+/// user_panic::add_fix_action("clear-cache", "Clear the cache directory", || {
+///     std::fs::remove_dir_all("cache")
+/// });
+/// ```
+pub fn add_fix_action<F, E>(name: &str, description: &str, action: F)
+where
+    F: Fn() -> Result<(), E> + Send + Sync + 'static,
+    E: Into<Box<dyn Error + Send + Sync>>,
+{
+    let mut actions = ACTIONS.write().unwrap_or_else(|e| e.into_inner());
+    actions.retain(|a| a.name != name);
+    actions.push(FixAction {
+        name: name.to_string(),
+        description: description.to_string(),
+        run: Arc::new(move || action().map_err(Into::into)),
+    });
+}
+
+// The outcome of a fix action that was run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Applied {
+    pub name: &'static str,
+    pub description: String,
+    pub error: Option<String>,
+}
+impl Applied {
+    pub(crate) fn text(&self, phrases: &Phrases) -> String {
+        match &self.error {
+            None => format!("{}: {}\n", phrases.fix_applied, self.description),
+            Some(e) => format!("{}: {}: {}\n", phrases.fix_failed, self.description, e),
+        }
+    }
+    // A single line object, like the JSON report
+    pub(crate) fn json(&self) -> String {
+        format!(
+            "{{\"fix_action\":{},\"applied\":{},\"error\":{}}}\n",
+            string(self.name),
+            self.error.is_none(),
+            self.error.as_deref().map_or("null".into(), string)
+        )
+    }
+}
+
+// Runs the registered actions of `panic` that `ask` agrees to
+pub(crate) fn apply(
+    panic: &UserPanic,
+    phrases: &Phrases,
+    ask: &mut dyn FnMut(&str) -> bool,
+) -> Vec<Applied> {
+    let mut applied = Vec::new();
+    for name in panic.fix_actions {
+        // The lock must not be held while an action runs
+        let found = ACTIONS
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .find(|a| a.name == *name)
+            .map(|a| (a.description.clone(), a.run.clone()));
+        let Some((description, run)) = found else {
+            log::debug!("fix action `{}` is not registered", name);
+            continue;
+        };
+        if !ask(&format!("{} \"{}\"? [y/N] ", phrases.run_fix, description)) {
+            continue;
+        }
+        applied.push(Applied {
+            name,
+            description,
+            error: run().err().map(|e| e.to_string()),
+        });
+    }
+    applied
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn registered_actions_run() {
+        static RUNS: AtomicUsize = AtomicUsize::new(0);
+        add_fix_action("test-count", "Count", || {
+            RUNS.fetch_add(1, Ordering::Relaxed);
+            Ok::<_, std::io::Error>(())
+        });
+        add_fix_action("test-fail", "Fail", || Err("disk full"));
+        let panic = UserPanic {
+            fix_actions: &["test-count", "test-missing", "test-fail"],
+            ..UserPanic::new("Broken", None)
+        };
+        let mut asked = Vec::new();
+        let applied = apply(&panic, &Phrases::ENGLISH, &mut |q| {
+            asked.push(q.to_string());
+            true
+        });
+        let lines: Vec<_> = applied.iter().map(|a| a.text(&Phrases::ENGLISH)).collect();
+        assert_eq!(
+            asked,
+            [
+                "Run the automatic fix \"Count\"? [y/N] ",
+                "Run the automatic fix \"Fail\"? [y/N] "
+            ]
+        );
+        assert_eq!(
+            lines,
+            ["Fix applied: Count\n", "Fix failed: Fail: disk full\n"]
+        );
+        assert_eq!(
+            applied[1].json(),
+            "{\"fix_action\":\"test-fail\",\"applied\":false,\"error\":\"disk full\"}\n"
+        );
+        assert!(apply(&panic, &Phrases::ENGLISH, &mut |_| false).is_empty());
+        assert_eq!(RUNS.load(Ordering::Relaxed), 1);
+    }
+}
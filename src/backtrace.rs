@@ -0,0 +1,117 @@
+//! Backtraces of user panics, kept for developers.
+//!
+//! Users only see the friendly message, the trace goes to the crash report,
+//! the JSON output and the text output at [`Verbosity::Technical`](crate::Verbosity).
+
+use std::backtrace::{Backtrace, BacktraceStatus};
+
+/// When the panic hook captures a backtrace for a [`UserPanic`](crate::UserPanic)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BacktraceMode {
+    /// When `RUST_BACKTRACE` or `RUST_LIB_BACKTRACE` ask for it, crash
+    /// reports always get one
+    #[default]
+    Env,
+    /// For every panic
+    Always,
+    /// Never, not even for crash reports
+    Never,
+}
+// The filtered backtrace of the current thread, `force` ignores the
+// environment unless backtraces are turned off
+pub(crate) fn capture(mode: BacktraceMode, force: bool) -> Option<String> {
+    let backtrace = match mode {
+        BacktraceMode::Never => return None,
+        BacktraceMode::Env if !force => Backtrace::capture(),
+        _ => Backtrace::force_capture(),
+    };
+    match backtrace.status() {
+        BacktraceStatus::Captured => Some(filter(&backtrace.to_string())),
+        _ => None,
+    }
+}
+
+// Crates whose frames say nothing about where the program went wrong
+const HIDDEN: [&str; 4] = ["std::", "core::", "alloc::", "user_panic::"];
+
+// Unwinding symbols of the runtime that are not under a crate path
+const RUNTIME: [&str; 4] = [
+    "rust_begin_unwind",
+    "rust_panic",
+    "__rust_begin_short_backtrace",
+    "__rust_end_short_backtrace",
+];
+
+// Drops the frames of the standard library and of this crate, a frame is its
+// numbered line followed by the indented `at` lines
+pub(crate) fn filter(backtrace: &str) -> String {
+    let mut frames: Vec<Vec<&str>> = Vec::new();
+    for line in backtrace.lines() {
+        let numbered = line
+            .trim_start()
+            .split_once(": ")
+            .is_some_and(|(n, _)| n.chars().all(|c| c.is_ascii_digit()));
+        match frames.last_mut() {
+            Some(frame) if !numbered => frame.push(line),
+            _ => frames.push(vec![line]),
+        }
+    }
+    let mut s = String::new();
+    for frame in frames.iter().filter(|f| !hidden(f)) {
+        for line in frame {
+            s += line;
+            s += "\n";
+        }
+    }
+    s
+}
+
+fn hidden(frame: &[&str]) -> bool {
+    let function = frame[0].trim_start().split_once(": ").map_or("", |f| f.1);
+    // Trait impls show up as `<&dyn core::ops::Fn<..> as ..>::call`
+    let function = function
+        .trim_start_matches(['<', '&'])
+        .trim_start_matches("dyn ");
+    HIDDEN.iter().any(|h| function.starts_with(h))
+        || RUNTIME.contains(&function)
+        // Sources of the standard library are under `/rustc/<commit>/library`
+        || frame[1..].iter().any(|l| l.contains("/rustc/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn std_frames_are_filtered() {
+        let trace = "   0: std::backtrace::Backtrace::force_capture
+             at /rustc/library/std/src/backtrace.rs:312:9
+   1: user_panic::panic_func
+             at ./src/lib.rs:500:5
+   2: <alloc::boxed::Box<F,A> as core::ops::function::Fn<Args>>::call
+   3: app::load_config
+             at ./src/main.rs:12:5
+   4: rust_begin_unwind
+   5: rust_app::run
+             at ./src/main.rs:8:5
+   6: <&dyn core::ops::function::Fn<(), Output = i32> as core::ops::function::FnOnce<()>>::call_once
+             at /rustc/59807616e/library/core/src/ops/function.rs:287:21
+   7: std::rt::lang_start::{{closure}}
+   8: __rust_begin_short_backtrace
+   9: app::main
+             at ./src/main.rs:4:5
+  10: __libc_start_main
+";
+        assert_eq!(
+            filter(trace),
+            "   3: app::load_config
+             at ./src/main.rs:12:5
+   5: rust_app::run
+             at ./src/main.rs:8:5
+   9: app::main
+             at ./src/main.rs:4:5
+  10: __libc_start_main
+"
+        );
+    }
+}
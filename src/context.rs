@@ -0,0 +1,320 @@
+//! What the panicking thread was doing, for the reports read by support.
+//!
+//! Every thread has a stack of the operations in flight, pushed with
+//! [`context`] and popped when the returned guard is dropped, and a trail of
+//! the last 20 operations and [`breadcrumb`]s. Async tasks wrapped in
+//! [`task_context`] carry their own.
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::Poll;
+
+// How many breadcrumbs every thread keeps
+const BREADCRUMBS: usize = 20;
+
+thread_local! {
+    static STACK: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    static TRAIL: RefCell<VecDeque<String>> = const { RefCell::new(VecDeque::new()) };
+}
+
+/// Marks the operation in flight on this thread until the guard is dropped,
+/// it is also left as a [`breadcrumb`]
+///
+/// The [`context!`](crate::context!) macro takes format arguments instead.
+///
+/// ```
+/// fn load_config(path: &str) {
+///     let _context = user_panic::context(format!("loading config {}", path));
+///     // A user panic here is reported with the context
+/// }
+/// ```
+///
+/// The stack belongs to the thread, a guard held across an `.await` ends up
+/// in the context of whatever task the thread runs next unless the task is
+/// wrapped in [`task_context`].
+pub fn context(text: impl Into<String>) -> ContextGuard {
+    let text = text.into();
+    breadcrumb(text.clone());
+    let depth = STACK.with(|stack| {
+        let mut stack = stack.borrow_mut();
+        stack.push(text);
+        stack.len() - 1
+    });
+    ContextGuard { depth }
+}
+
+/// Leaves a breadcrumb on this thread, only the last 20 are kept
+pub fn breadcrumb(text: impl Into<String>) {
+    TRAIL.with(|trail| {
+        let mut trail = trail.borrow_mut();
+        if trail.len() == BREADCRUMBS {
+            trail.pop_front();
+        }
+        trail.push_back(text.into());
+    });
+}
+
+/// Pushes a context with format arguments, see [`context()`](crate::context())
+///
+/// ```
+/// let path = "app.toml";
+/// let _context = user_panic::context!("loading config {}", path);
+/// ```
+#[macro_export]
+macro_rules! context {
+    ($($arg:tt)*) => {
+        $crate::context(format!($($arg)*))
+    };
+}
+
+/// Removes a context from the stack of its thread when dropped
+///
+/// Guards are `Send` so tasks can hold them across an `.await`, the stack of
+/// a [`task_context`] is put back on whatever thread polls or drops it.
+#[derive(Debug)]
+#[must_use = "the context is removed when the guard is dropped"]
+pub struct ContextGuard {
+    depth: usize,
+}
+impl Drop for ContextGuard {
+    fn drop(&mut self) {
+        // Also removes the contexts of guards that were leaked on top of it
+        let _ = STACK.try_with(|stack| stack.borrow_mut().truncate(self.depth));
+    }
+}
+
+/// Gives `future` its own context stack and breadcrumbs, which follow it
+/// across threads and `.await`s
+///
+/// The task starts with a copy of the ones of the thread creating it, what it
+/// adds is only seen while it is polled.
+///
+/// ```
+/// async fn sync(id: u32) {
+///     let _context = user_panic::context!("syncing account {}", id);
+///     // A user panic after an `.await` is still reported with the context
+/// }
+/// // Handed to the spawn function of the runtime
+/// let task = user_panic::task_context(sync(7));
+/// ```
+pub fn task_context<F: Future>(future: F) -> TaskContext<F> {
+    TaskContext {
+        future: Some(Box::pin(future)),
+        stack: STACK
+            .try_with(|stack| stack.borrow().clone())
+            .unwrap_or_default(),
+        trail: TRAIL
+            .try_with(|trail| trail.borrow().clone())
+            .unwrap_or_default(),
+    }
+}
+
+/// A future with its own context, see [`task_context`]
+#[must_use = "futures do nothing unless polled"]
+pub struct TaskContext<F> {
+    // Only `None` once it is dropped
+    future: Option<Pin<Box<F>>>,
+    stack: Vec<String>,
+    trail: VecDeque<String>,
+}
+impl<F: Future> Future for TaskContext<F> {
+    type Output = F::Output;
+    fn poll(self: Pin<&mut Self>, cx: &mut std::task::Context) -> Poll<F::Output> {
+        let this = self.get_mut();
+        let _swapped = Swapped::new(&mut this.stack, &mut this.trail);
+        let future = this
+            .future
+            .as_mut()
+            .expect("the future is only taken on drop");
+        future.as_mut().poll(cx)
+    }
+}
+// A cancelled task drops its guards, they must not touch the stack of the thread
+impl<F> Drop for TaskContext<F> {
+    fn drop(&mut self) {
+        let _swapped = Swapped::new(&mut self.stack, &mut self.trail);
+        drop(self.future.take());
+    }
+}
+
+// Puts the context of a task on the thread while it is polled and takes it
+// back afterwards, also when the poll unwinds
+struct Swapped<'a> {
+    stack: &'a mut Vec<String>,
+    trail: &'a mut VecDeque<String>,
+}
+impl<'a> Swapped<'a> {
+    fn new(stack: &'a mut Vec<String>, trail: &'a mut VecDeque<String>) -> Self {
+        let mut swapped = Swapped { stack, trail };
+        swapped.swap();
+        swapped
+    }
+    fn swap(&mut self) {
+        let _ = STACK.try_with(|stack| std::mem::swap(&mut *stack.borrow_mut(), self.stack));
+        let _ = TRAIL.try_with(|trail| std::mem::swap(&mut *trail.borrow_mut(), self.trail));
+    }
+}
+impl Drop for Swapped<'_> {
+    fn drop(&mut self) {
+        self.swap();
+    }
+}
+
+// Everything known about the current thread when a panic is reported
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Context {
+    pub thread: Option<String>,
+    /// The outermost operation first
+    pub stack: Vec<String>,
+    /// The oldest breadcrumb first
+    pub breadcrumbs: Vec<String>,
+}
+impl Context {
+    pub(crate) fn current() -> Context {
+        Context {
+            thread: std::thread::current().name().map(String::from),
+            stack: STACK
+                .try_with(|stack| stack.borrow().clone())
+                .unwrap_or_default(),
+            breadcrumbs: TRAIL
+                .try_with(|trail| trail.borrow().iter().cloned().collect())
+                .unwrap_or_default(),
+        }
+    }
+    // The text shown at `Verbosity::Technical` and in crash reports
+    pub(crate) fn text(&self) -> String {
+        let mut s = String::new();
+        if let Some(thread) = &self.thread {
+            s += &format!("Thread: {}\n", thread);
+        }
+        for (heading, lines) in [("Context", &self.stack), ("Breadcrumbs", &self.breadcrumbs)] {
+            if !lines.is_empty() {
+                s += &format!("{}:\n", heading);
+                for line in lines {
+                    s += &format!("\t{}\n", line);
+                }
+            }
+        }
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stack_and_breadcrumbs() {
+        // Tests run on their own threads, which start without a context
+        std::thread::Builder::new()
+            .name("worker".into())
+            .spawn(|| {
+                breadcrumb("started");
+                let outer = context!("loading config {}", "app.toml");
+                {
+                    let _inner = context("parsing");
+                    assert_eq!(
+                        Context::current().stack,
+                        vec!["loading config app.toml", "parsing"]
+                    );
+                }
+                assert_eq!(
+                    Context::current().text(),
+                    "Thread: worker\nContext:\n\tloading config app.toml\n\
+                     Breadcrumbs:\n\tstarted\n\tloading config app.toml\n\tparsing\n"
+                );
+                drop(outer);
+                for i in 0..BREADCRUMBS {
+                    breadcrumb(i.to_string());
+                }
+                let context = Context::current();
+                assert!(context.stack.is_empty());
+                assert_eq!(context.breadcrumbs.len(), BREADCRUMBS);
+                assert_eq!(context.breadcrumbs[0], "0");
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+    }
+
+    // Polls a task by hand, nothing wakes it up
+    fn noop_waker() -> std::task::Waker {
+        struct Noop;
+        impl std::task::Wake for Noop {
+            fn wake(self: std::sync::Arc<Self>) {}
+        }
+        std::sync::Arc::new(Noop).into()
+    }
+
+    // Pending on the first poll like a task waiting for io
+    struct YieldOnce(bool);
+    impl Future for YieldOnce {
+        type Output = ();
+        fn poll(mut self: Pin<&mut Self>, _: &mut std::task::Context) -> Poll<()> {
+            match std::mem::replace(&mut self.0, true) {
+                true => Poll::Ready(()),
+                false => Poll::Pending,
+            }
+        }
+    }
+
+    #[test]
+    fn tasks_keep_their_context() {
+        std::thread::spawn(|| {
+            let _serving = context("serving");
+            let mut task = task_context(async {
+                let _syncing = context("syncing");
+                YieldOnce(false).await;
+                Context::current()
+            });
+            let waker = noop_waker();
+            let mut cx = std::task::Context::from_waker(&waker);
+            assert!(Pin::new(&mut task).poll(&mut cx).is_pending());
+            assert_eq!(Context::current().stack, ["serving"]);
+            // Another task running on the thread in between
+            let _other = context("other task");
+            let Poll::Ready(inside) = Pin::new(&mut task).poll(&mut cx) else {
+                panic!("the task should be done");
+            };
+            assert_eq!(inside.stack, ["serving", "syncing"]);
+            assert_eq!(inside.breadcrumbs, ["serving", "syncing"]);
+            let outside = Context::current();
+            assert_eq!(outside.stack, ["serving", "other task"]);
+            assert_eq!(outside.breadcrumbs, ["serving", "other task"]);
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[test]
+    fn tasks_holding_guards_can_be_spawned() {
+        // Like the bound of `tokio::spawn`
+        fn spawn<F: Future + Send + 'static>(_: F) {}
+        spawn(task_context(async {
+            let _syncing = context("syncing");
+            YieldOnce(false).await;
+        }));
+    }
+
+    #[test]
+    fn cancelled_tasks_keep_the_thread_context() {
+        std::thread::spawn(|| {
+            let _serving = context("serving");
+            let mut task = task_context(async {
+                let _syncing = context("syncing");
+                YieldOnce(false).await;
+            });
+            let waker = noop_waker();
+            let mut cx = std::task::Context::from_waker(&waker);
+            assert!(Pin::new(&mut task).poll(&mut cx).is_pending());
+            let _other = context("other task");
+            // Like a timeout running out while the task waits
+            drop(task);
+            assert_eq!(Context::current().stack, ["serving", "other task"]);
+        })
+        .join()
+        .unwrap();
+    }
+}
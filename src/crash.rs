@@ -0,0 +1,266 @@
+//! Crash report files written next to the console output.
+//!
+//! A report holds everything a developer needs to look into a bug report:
+//! the message, the fix steps, where the panic happened, a backtrace and the
+//! program version, platform and arguments.
+
+use crate::backtrace::{self, BacktraceMode};
+use crate::context::Context;
+use crate::json::rfc3339;
+use crate::UserPanic;
+use std::fs::{File, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Where and how crash reports are saved
+///
+/// ```
+/// use user_panic::CrashReports;
+///
+/// user_panic::HookConfig::new()
+///     .crash_reports(CrashReports::in_state_dir().version(env!("CARGO_PKG_VERSION")))
+///     .install()
+///     .keep();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CrashReports {
+    // `None` for the state directory
+    dir: Option<PathBuf>,
+    version: Option<&'static str>,
+}
+impl CrashReports {
+    /// Reports saved in `dir`, which is created when missing
+    pub fn in_dir(dir: impl Into<PathBuf>) -> Self {
+        CrashReports {
+            dir: Some(dir.into()),
+            version: None,
+        }
+    }
+    /// Reports saved in a directory named after the executable in
+    /// `$XDG_STATE_HOME`, `~/.local/state` or `%LOCALAPPDATA%`
+    pub fn in_state_dir() -> Self {
+        Self::default()
+    }
+    /// The version of the program written in the reports
+    pub fn version(mut self, version: &'static str) -> Self {
+        self.version = Some(version);
+        self
+    }
+    /// The directory the reports are saved in, `None` if it can't be found
+    pub fn dir(&self) -> Option<PathBuf> {
+        if let Some(dir) = &self.dir {
+            return Some(dir.clone());
+        }
+        let env_dir = |var| {
+            std::env::var_os(var)
+                .filter(|v| !v.is_empty())
+                .map(PathBuf::from)
+        };
+        let base = env_dir("XDG_STATE_HOME")
+            .or_else(|| env_dir("HOME").map(|home| home.join(".local").join("state")))
+            .or_else(|| env_dir("LOCALAPPDATA"))?;
+        Some(base.join(program()))
+    }
+}
+
+// Writes the report, returning its path
+pub(crate) fn save(
+    reports: &CrashReports,
+    mode: BacktraceMode,
+    panic: &UserPanic,
+    location: Option<String>,
+    context: &Context,
+    time: SystemTime,
+    backtrace: Option<&str>,
+) -> Option<PathBuf> {
+    let dir = reports.dir()?;
+    std::fs::create_dir_all(&dir).ok()?;
+    // Colons are not allowed in windows file names
+    let name = format!(
+        "crash-{}-{}",
+        rfc3339(time).replace(':', "-"),
+        std::process::id()
+    );
+    let (path, mut file) = create(&dir, &name)?;
+    // The report gets a backtrace even if the output doesn't
+    let backtrace = backtrace
+        .map(String::from)
+        .or_else(|| backtrace::capture(mode, true));
+    let text = contents(
+        panic,
+        reports,
+        location.as_deref(),
+        context,
+        time,
+        backtrace.as_deref(),
+    );
+    file.write_all(text.as_bytes()).ok()?;
+    Some(path)
+}
+
+// Opens a new report file, reports saved in the same second get a counter
+// instead of replacing each other
+fn create(dir: &Path, name: &str) -> Option<(PathBuf, File)> {
+    for i in 0u32.. {
+        let path = match i {
+            0 => dir.join(format!("{}.txt", name)),
+            i => dir.join(format!("{}-{}.txt", name, i)),
+        };
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(file) => return Some((path, file)),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => {}
+            Err(_) => return None,
+        }
+    }
+    None
+}
+
+// The text of a report, also what the interactive menu copies
+pub(crate) fn contents(
+    panic: &UserPanic,
+    reports: &CrashReports,
+    location: Option<&str>,
+    context: &Context,
+    time: SystemTime,
+    backtrace: Option<&str>,
+) -> String {
+    let mut s = format!("Crash report of {}\n\n", program());
+    s += &format!("Time: {}\n", rfc3339(time));
+    if let Some(version) = reports.version {
+        s += &format!("Version: {}\n", version);
+    }
+    s += &format!("user-panic: {}\n", env!("CARGO_PKG_VERSION"));
+    s += &format!(
+        "Platform: {} {}\n",
+        std::env::consts::OS,
+        std::env::consts::ARCH
+    );
+    // `args` would panic inside the hook on arguments that are not UTF-8
+    let args: Vec<String> = std::env::args_os()
+        .map(|a| a.to_string_lossy().into_owned())
+        .collect();
+    s += &format!("Args: {:?}\n", args);
+    if let Some(location) = location {
+        s += &format!("Location: {}\n", location);
+    }
+    if !panic.key.is_empty() {
+        s += &format!("Key: {}\n", panic.key);
+    }
+    if panic.code != 0 {
+        s += &format!("Code: {}\n", panic.code);
+    }
+    s += &format!("Severity: {}\n", panic.severity);
+    if let Some(category) = panic.category {
+        s += &format!("Category: {}\n", category);
+    }
+    s += &format!("\n{}", panic);
+    if let Some(details) = panic.technical_details() {
+        s += &format!("\n{}", details);
+    }
+    let context = context.text();
+    if !context.is_empty() {
+        s += &format!("\n{}", context);
+    }
+    if let Some(backtrace) = backtrace {
+        s += &format!("\nBacktrace:\n{}", backtrace);
+    }
+    s
+}
+
+// Name of the executable without its extension
+fn program() -> String {
+    std::env::current_exe()
+        .ok()
+        .as_deref()
+        .and_then(Path::file_stem)
+        .map_or("program".into(), |name| name.to_string_lossy().into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FixStep;
+    use std::time::UNIX_EPOCH;
+
+    #[test]
+    fn report_contents() {
+        let err = UserPanic {
+            key: "API",
+            code: 4,
+            category: Some("network"),
+            ..UserPanic::new(
+                "API error",
+                Some(&[FixStep {
+                    text: "Check the connection",
+                    children: &[],
+                }]),
+            )
+        };
+        let reports = CrashReports::in_dir("crashes").version("1.2.3");
+        let err = err.with_source("timed out");
+        let context = Context {
+            thread: Some("main".into()),
+            stack: vec!["syncing".into()],
+            breadcrumbs: Vec::new(),
+        };
+        let s = contents(
+            &err,
+            &reports,
+            Some("src/main.rs:4:7"),
+            &context,
+            UNIX_EPOCH,
+            Some("   0: app::main\n"),
+        );
+        assert!(
+            s.contains("\n\nTime: 1970-01-01T00:00:00Z\nVersion: 1.2.3\n"),
+            "{}",
+            s
+        );
+        assert!(
+            s.contains(&format!("Platform: {} ", std::env::consts::OS)),
+            "{}",
+            s
+        );
+        assert!(
+            s.contains("Location: src/main.rs:4:7\nKey: API\nCode: 4\nSeverity: fatal\nCategory: network\n\nThe Program Crashed\n"),
+            "{}",
+            s
+        );
+        assert!(
+            s.contains("\t1: Check the connection\n\nTechnical details:\n\t0: timed out\n\nThread: main\nContext:\n\tsyncing\n"),
+            "{}",
+            s
+        );
+        assert!(s.ends_with("\nBacktrace:\n   0: app::main\n"), "{}", s);
+        let s = contents(&err, &reports, None, &Context::default(), UNIX_EPOCH, None);
+        assert!(!s.contains("Backtrace"), "{}", s);
+        assert!(!s.contains("Thread"), "{}", s);
+        assert_eq!(reports.dir(), Some(PathBuf::from("crashes")));
+    }
+
+    #[test]
+    fn reports_saved_together_are_kept() {
+        let dir = std::env::temp_dir().join(format!("user-panic-crashes-{}", std::process::id()));
+        let reports = CrashReports::in_dir(&dir);
+        let err = UserPanic::new("Broken", None);
+        let save = || {
+            save(
+                &reports,
+                BacktraceMode::Never,
+                &err,
+                None,
+                &Context::default(),
+                UNIX_EPOCH,
+                None,
+            )
+            .unwrap()
+        };
+        let (first, second) = (save(), save());
+        assert_ne!(first, second);
+        assert!(second.to_string_lossy().ends_with("-1.txt"), "{:?}", second);
+        assert!(std::fs::read_to_string(&first).unwrap().contains("Broken"));
+        assert!(std::fs::read_to_string(&second).unwrap().contains("Broken"));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
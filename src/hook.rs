@@ -0,0 +1,649 @@
+//! The panic hook and everything it can be configured with.
+
+use crate::action::{self, FixActionMode};
+use crate::backtrace::{self, BacktraceMode};
+use crate::context::Context;
+use crate::prompt::{self, Choice};
+use crate::{crash, json, locale, CrashReports, DefaultTemplate, ReportTemplate, UserPanic};
+use crate::{BugTracker, PanicSink, StderrSink, Terminal, WriteSink};
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::io::{IsTerminal, Write};
+use std::panic::{self, Location, PanicHookInfo};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::SystemTime;
+
+type Panicfn = Box<dyn Fn(&PanicHookInfo) + Sync + Send>;
+
+// The installed configurations, the last one handles the panics
+struct Installed {
+    configs: Vec<(u64, Arc<HookConfig>)>,
+    // The hook that was set before, `Some` while the hook of this crate is set
+    original: Option<Arc<Panicfn>>,
+    next_id: u64,
+}
+static INSTALLED: Mutex<Installed> = Mutex::new(Installed {
+    configs: Vec::new(),
+    original: None,
+    next_id: 0,
+});
+
+// What the interactive menu needs of a reported panic, the context guards are
+// gone once it unwound
+struct Menu {
+    location: Option<String>,
+    context: Context,
+    time: SystemTime,
+    backtrace: Option<String>,
+    saved: bool,
+}
+thread_local! {
+    // Set while `run` calls its closure, it shows the menu after catching the
+    // panic so it can offer to retry
+    static DEFERRED: Cell<bool> = const { Cell::new(false) };
+    static MENU: RefCell<Option<Menu>> = const { RefCell::new(None) };
+}
+
+fn installed() -> MutexGuard<'static, Installed> {
+    INSTALLED.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// How much the panic hook prints besides the message meant for users
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Verbosity {
+    /// Only the message and the fix instructions
+    #[default]
+    User,
+    /// Also the chain of source errors attached with [`UserPanic::with_source`],
+    /// the [`context`](crate::context()) of the thread and the backtrace if
+    /// one was captured, see [`BacktraceMode`]
+    Technical,
+}
+
+/// What the panic hook prints for a [`UserPanic`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Format {
+    /// The report meant for people
+    #[default]
+    Text,
+    /// A single line JSON object with the code, key, message, fix steps,
+    /// developer message, location, thread name, context and timestamp
+    ///
+    /// A missing code or key is `null`. Other panics get an object with a
+    /// `null` code and key and silent panics print nothing, so every line of
+    /// the output is an object.
+    Json,
+}
+
+/// Whether the output is styled with ANSI colors
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorChoice {
+    /// Only when writing to stderr and it is a terminal, unless `NO_COLOR` is set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+/// Configuration of the panic hook, applied with [`HookConfig::install`]
+///
+/// ```
+/// use user_panic::{Format, HookConfig, Verbosity};
+///
+/// HookConfig::new()
+///     .developer("If the error still persists\nContact the developer at xyz@wkl.com")
+///     .verbosity(Verbosity::Technical)
+///     .format(Format::Text)
+///     .install()
+///     .keep();
+/// ```
+pub struct HookConfig {
+    developer: Option<String>,
+    sinks: Vec<Box<dyn PanicSink>>,
+    format: Format,
+    color: ColorChoice,
+    chain: bool,
+    exit: bool,
+    crash_reports: Option<CrashReports>,
+    verbosity: Verbosity,
+    backtraces: BacktraceMode,
+    fix_actions: FixActionMode,
+    interactive: bool,
+    bug_tracker: Option<BugTracker>,
+    template: Box<dyn ReportTemplate>,
+}
+impl Default for HookConfig {
+    fn default() -> Self {
+        HookConfig {
+            developer: None,
+            sinks: Vec::new(),
+            format: Format::Text,
+            color: ColorChoice::Auto,
+            chain: true,
+            exit: false,
+            crash_reports: None,
+            verbosity: Verbosity::User,
+            backtraces: BacktraceMode::Env,
+            fix_actions: FixActionMode::Never,
+            interactive: false,
+            bug_tracker: None,
+            template: Box::new(DefaultTemplate),
+        }
+    }
+}
+impl HookConfig {
+    /// The configuration used by [`set_hooks`]
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Message shown after every panic, like how to contact the developer
+    pub fn developer(mut self, developer: impl Into<String>) -> Self {
+        self.developer = Some(developer.into());
+        self
+    }
+    /// Sends the output to `sink` as well, the first sink replaces stderr
+    pub fn sink(mut self, sink: impl PanicSink + 'static) -> Self {
+        self.sinks.push(Box::new(sink));
+        self
+    }
+    /// Same as [`sink`](Self::sink) with a [`WriteSink`]
+    pub fn writer(self, writer: impl Write + Send + 'static) -> Self {
+        self.sink(WriteSink::new(writer))
+    }
+    /// What is printed for a [`UserPanic`], defaults to [`Format::Text`]
+    ///
+    /// The `USER_PANIC_FORMAT` environment variable set to `json` or `text`
+    /// takes precedence.
+    pub fn format(mut self, format: Format) -> Self {
+        self.format = format;
+        self
+    }
+    /// Whether the output is styled, defaults to [`ColorChoice::Auto`] which
+    /// leaves it to each [`PanicSink`]
+    pub fn color(mut self, color: ColorChoice) -> Self {
+        self.color = color;
+        self
+    }
+    /// Whether other panics go to the hook that was set before, defaults to
+    /// `true`, otherwise they get a short message in the same output
+    ///
+    /// With [`Format::Json`] they are always reported as a JSON object instead.
+    pub fn chain(mut self, chain: bool) -> Self {
+        self.chain = chain;
+        self
+    }
+    /// Whether the process exits with [`UserPanic::exit_code`] right after
+    /// reporting a [`UserPanic`] instead of unwinding, defaults to `false`
+    pub fn exit(mut self, exit: bool) -> Self {
+        self.exit = exit;
+        self
+    }
+    /// Saves a crash report for every [`UserPanic`] and prints its path
+    pub fn crash_reports(mut self, reports: CrashReports) -> Self {
+        self.crash_reports = Some(reports);
+        self
+    }
+    /// How much is printed, defaults to [`Verbosity::User`]
+    pub fn verbosity(mut self, verbosity: Verbosity) -> Self {
+        self.verbosity = verbosity;
+        self
+    }
+    /// When backtraces are captured, defaults to [`BacktraceMode::Env`]
+    pub fn backtraces(mut self, mode: BacktraceMode) -> Self {
+        self.backtraces = mode;
+        self
+    }
+    /// Whether the fix actions of a [`UserPanic`] are run after reporting it,
+    /// defaults to [`FixActionMode::Never`]
+    ///
+    /// The outcome of every action is sent to the sinks as well.
+    pub fn fix_actions(mut self, mode: FixActionMode) -> Self {
+        self.fix_actions = mode;
+        self
+    }
+    /// Whether a menu is shown after reporting a [`UserPanic`], defaults to
+    /// `false`
+    ///
+    /// The menu offers to copy the report to the clipboard, save a crash
+    /// report, open the [`bug_tracker`](Self::bug_tracker) or quit with
+    /// [`UserPanic::exit_code`]. Panics caught by [`run`](crate::run) can also
+    /// be retried, which calls its closure again. The menu is only shown when
+    /// stdin and stderr are terminals.
+    pub fn interactive(mut self, interactive: bool) -> Self {
+        self.interactive = interactive;
+        self
+    }
+    /// Where users report bugs, a url or a [`BugTracker`] template
+    ///
+    /// The filled in link is printed below errors without fix instructions and
+    /// opened from the [`interactive`](Self::interactive) menu.
+    pub fn bug_tracker(mut self, tracker: impl Into<BugTracker>) -> Self {
+        self.bug_tracker = Some(tracker.into());
+        self
+    }
+    /// Lays out the text report, defaults to [`DefaultTemplate`]
+    pub fn template(mut self, template: impl ReportTemplate + 'static) -> Self {
+        self.template = Box::new(template);
+        self
+    }
+    /// Sets the panic hook until the returned guard is dropped
+    ///
+    /// Installing again while the hook is set doesn't wrap it a second time,
+    /// the newest configuration is used until its guard is dropped.
+    pub fn install(mut self) -> HookGuard {
+        if self.sinks.is_empty() {
+            self.sinks.push(Box::new(StderrSink));
+        }
+        let mut installed = installed();
+        if installed.original.is_none() {
+            installed.original = Some(Arc::new(panic::take_hook()));
+            panic::set_hook(Box::new(dispatch));
+        }
+        installed.next_id += 1;
+        let id = installed.next_id;
+        installed.configs.push((id, Arc::new(self)));
+        HookGuard { id }
+    }
+
+    fn format_in_use(&self) -> Format {
+        match std::env::var("USER_PANIC_FORMAT").as_deref() {
+            Ok("json") => Format::Json,
+            Ok("text") => Format::Text,
+            _ => self.format,
+        }
+    }
+    fn terminal(&self, sink: &dyn PanicSink) -> Terminal {
+        let mut terminal = sink.terminal();
+        match self.color {
+            ColorChoice::Auto => {}
+            ColorChoice::Always => terminal.color = true,
+            ColorChoice::Never => terminal.color = false,
+        }
+        terminal
+    }
+
+    // The panic function
+    fn handle(&self, panic_info: &PanicHookInfo, original: &Panicfn) {
+        // Default to original panic routine if downcast_ref fails, its text
+        // would break the one object per line of the JSON format
+        if !panic_info.payload().is::<UserPanic>()
+            && self.chain
+            && self.format_in_use() != Format::Json
+        {
+            original(panic_info);
+        }
+        self.report(panic_info.payload(), panic_info.location());
+    }
+    fn report(&self, payload: &(dyn Any + Send), location: Option<&Location>) {
+        let panic = payload.downcast_ref::<UserPanic>();
+        // Panics without a message are silent
+        let shown = panic.filter(|p| !p.error_msg.is_empty());
+        let time = SystemTime::now();
+        let context = Context::current();
+        let backtrace = shown.and_then(|_| backtrace::capture(self.backtraces, false));
+        let report =
+            shown.and_then(|p| self.save(p, location, &context, time, backtrace.as_deref()));
+        let bug_url = shown
+            .filter(|p| p.fix_instructions.is_none())
+            .and_then(|p| self.bug_url(p, backtrace.as_deref()));
+        let json = self.format_in_use() == Format::Json;
+        for sink in &self.sinks {
+            let terminal = self.terminal(sink.as_ref());
+            let output = match (panic, shown) {
+                // The developer message is part of the object
+                (_, Some(p)) if json => {
+                    let json = json::report(
+                        p,
+                        self.developer.as_deref(),
+                        location,
+                        &context,
+                        time,
+                        backtrace.as_deref(),
+                        report.as_deref(),
+                    );
+                    format!("{}\n", json)
+                }
+                (None, _) if json => {
+                    let json = json::foreign(
+                        payload_message(payload),
+                        self.developer.as_deref(),
+                        location,
+                        &context,
+                        time,
+                    );
+                    format!("{}\n", json)
+                }
+                // Silent panics have nothing to put in an object
+                (Some(_), None) if json => continue,
+                (_, Some(p)) => self.text(
+                    p,
+                    terminal,
+                    &context,
+                    backtrace.as_deref(),
+                    report.as_ref(),
+                    bug_url.as_deref(),
+                ),
+                (None, _) if !self.chain => {
+                    plain_message(payload, location) + &self.footer(terminal)
+                }
+                _ => self.footer(terminal),
+            };
+            // The original hook already printed the panic
+            if output.is_empty() {
+                continue;
+            }
+            sink.write(&output, panic);
+        }
+        if let Some(p) = shown {
+            self.fix(p, json);
+        }
+        let menu = shown.map(|_| Menu {
+            location: location.map(|l| l.to_string()),
+            context,
+            time,
+            backtrace,
+            saved: report.is_some(),
+        });
+        let deferred = DEFERRED.with(Cell::get);
+        match (shown, menu) {
+            // Silent panics leave no menu behind either
+            (_, menu) if deferred && panic.is_some() => MENU.with(|m| *m.borrow_mut() = menu),
+            (Some(p), Some(menu)) => {
+                self.prompt(p, &menu, false);
+            }
+            _ => {}
+        }
+        // Exiting here skips unwinding, `run` exits by itself
+        if let Some(panic) = panic.filter(|_| self.exit && !deferred) {
+            std::process::exit(panic.exit_code());
+        }
+    }
+    // The report of a user panic for people
+    fn text(
+        &self,
+        panic: &UserPanic,
+        terminal: Terminal,
+        context: &Context,
+        backtrace: Option<&str>,
+        report: Option<&PathBuf>,
+        bug_url: Option<&str>,
+    ) -> String {
+        let candidates = locale::candidates(locale::locale().as_deref());
+        let phrases = locale::phrases(&candidates);
+        let mut s = panic.render(self.template.as_ref(), &candidates, terminal);
+        s += "\n";
+        if self.verbosity >= Verbosity::Technical {
+            if let Some(details) = panic.details(phrases.technical_details) {
+                s += &format!("{}\n", details);
+            }
+            let context = context.text();
+            if !context.is_empty() {
+                s += &format!("{}\n", context);
+            }
+            if let Some(backtrace) = backtrace {
+                s += &format!("Backtrace:\n{}\n", backtrace);
+            }
+        }
+        s += &self.footer(terminal);
+        if let Some(path) = report {
+            s += &format!("{} {}\n", phrases.report_saved, path.display());
+        }
+        if let Some(url) = bug_url {
+            s += &format!("{} {}\n", phrases.report_bug, url);
+        }
+        s
+    }
+    // Runs the fix actions allowed by the mode and reports how they went
+    fn fix(&self, panic: &UserPanic, json: bool) {
+        let ask = match self.fix_actions {
+            _ if panic.fix_actions.is_empty() => return,
+            FixActionMode::Never => return,
+            FixActionMode::Ask if !std::io::stdin().is_terminal() => return,
+            FixActionMode::Ask => true,
+            FixActionMode::Always => false,
+        };
+        let phrases = locale::phrases(&locale::candidates(locale::locale().as_deref()));
+        let applied = action::apply(panic, &phrases, &mut |question| !ask || confirm(question));
+        for applied in applied {
+            let output = match json {
+                true => applied.json(),
+                false => applied.text(&phrases),
+            };
+            for sink in &self.sinks {
+                sink.write(&output, Some(panic));
+            }
+        }
+    }
+    // The interactive menu, when there is someone to answer it, returns whether
+    // the user chose to retry
+    fn prompt(&self, panic: &UserPanic, menu: &Menu, retry: bool) -> bool {
+        let terminals = std::io::stdin().is_terminal() && std::io::stderr().is_terminal();
+        if !self.interactive || !terminals {
+            return false;
+        }
+        let phrases = locale::phrases(&locale::candidates(locale::locale().as_deref()));
+        let reports = self.crash_reports.clone().unwrap_or_default();
+        let backtrace = menu.backtrace.as_deref();
+        let bug_url = self.bug_url(panic, backtrace);
+        let mut choices = Vec::new();
+        if retry {
+            choices.push(Choice::Retry);
+        }
+        choices.push(Choice::Copy);
+        if !menu.saved {
+            choices.push(Choice::Save);
+        }
+        if bug_url.is_some() {
+            choices.push(Choice::Open);
+        }
+        choices.push(Choice::Quit);
+        let mut act = |choice| match choice {
+            Choice::Copy => {
+                let text = crash::contents(
+                    panic,
+                    &reports,
+                    menu.location.as_deref(),
+                    &menu.context,
+                    menu.time,
+                    backtrace,
+                );
+                Some(match prompt::copy(&text) {
+                    true => phrases.report_copied.to_string(),
+                    false => phrases.report_not_copied.to_string(),
+                })
+            }
+            Choice::Save => {
+                let path = crash::save(
+                    &reports,
+                    self.backtraces,
+                    panic,
+                    menu.location.clone(),
+                    &menu.context,
+                    menu.time,
+                    backtrace,
+                )?;
+                Some(format!("{} {}", phrases.report_saved, path.display()))
+            }
+            // The url can still be copied from the terminal
+            Choice::Open => {
+                let url = bug_url.as_deref()?;
+                (!prompt::open(url)).then(|| url.to_string())
+            }
+            Choice::Retry | Choice::Quit => None,
+        };
+        let choice = prompt::ask(
+            &choices,
+            &phrases,
+            &mut std::io::stdin().lock(),
+            &mut std::io::stderr(),
+            &mut act,
+        );
+        if choice == Some(Choice::Quit) {
+            std::process::exit(panic.exit_code());
+        }
+        choice == Some(Choice::Retry)
+    }
+    // The filled in link to the bug tracker, if there is one
+    fn bug_url(&self, panic: &UserPanic, backtrace: Option<&str>) -> Option<String> {
+        let tracker = self.bug_tracker.as_ref()?;
+        // The report is for developers, so it always gets a backtrace
+        let backtrace = backtrace
+            .map(String::from)
+            .or_else(|| backtrace::capture(self.backtraces, true));
+        Some(tracker.url(panic, backtrace.as_deref()))
+    }
+    // The developer message is shown for every panic, if there is one
+    fn footer(&self, terminal: Terminal) -> String {
+        self.template.footer(self.developer.as_deref(), &terminal)
+    }
+    fn save(
+        &self,
+        err: &UserPanic,
+        location: Option<&Location>,
+        context: &Context,
+        time: SystemTime,
+        backtrace: Option<&str>,
+    ) -> Option<PathBuf> {
+        let reports = self.crash_reports.as_ref()?;
+        let location = location.map(|l| l.to_string());
+        crash::save(
+            reports,
+            self.backtraces,
+            err,
+            location,
+            context,
+            time,
+            backtrace,
+        )
+    }
+}
+
+/// Keeps the panic hook set, dropping it restores the configuration or the
+/// hook that was used before
+///
+/// ```
+/// {
+///     let _hooks = user_panic::HookConfig::new().developer("Mail xyz").install();
+///     // Panics here are reported by user_panic
+/// }
+/// // And here by the hook set before
+/// ```
+#[derive(Debug)]
+#[must_use = "the previous panic hook is restored when the guard is dropped"]
+pub struct HookGuard {
+    id: u64,
+}
+impl HookGuard {
+    /// Leaves the hook set for the rest of the program
+    pub fn keep(self) {
+        std::mem::forget(self);
+    }
+}
+impl Drop for HookGuard {
+    fn drop(&mut self) {
+        let mut installed = installed();
+        installed.configs.retain(|(id, _)| *id != self.id);
+        // The hook can't be changed while panicking, until the next
+        // drop the panics go to the original hook instead
+        if installed.configs.is_empty() && !std::thread::panicking() {
+            if let Some(original) = installed.original.take() {
+                panic::set_hook(Box::new(move |pan_inf| original(pan_inf)));
+            }
+        }
+    }
+}
+
+// Whether a configuration is installed
+pub(crate) fn is_installed() -> bool {
+    !installed().configs.is_empty()
+}
+
+// Reports `panic` with the installed configuration as if it was raised at
+// `location`, without panicking
+pub(crate) fn report(panic: &UserPanic, location: &Location) {
+    let config = installed().configs.last().map(|(_, config)| config.clone());
+    match config {
+        Some(config) => config.report(panic, Some(location)),
+        None => HookConfig::new()
+            .sink(StderrSink)
+            .report(panic, Some(location)),
+    }
+}
+
+// Sets whether `run` shows the menu instead of the hook on this thread,
+// returns the previous setting
+pub(crate) fn defer_menu(deferred: bool) -> bool {
+    DEFERRED.with(|d| d.replace(deferred))
+}
+
+// Shows the menu of the last panic reported on this thread with a retry
+// choice, returns whether the user chose to retry
+pub(crate) fn ask_retry(panic: &UserPanic) -> bool {
+    let menu = MENU.with(|m| m.borrow_mut().take());
+    let config = installed().configs.last().map(|(_, config)| config.clone());
+    match (menu, config) {
+        (Some(menu), Some(config)) => config.prompt(panic, &menu, true),
+        _ => false,
+    }
+}
+
+// The hook set by this crate
+fn dispatch(panic_info: &PanicHookInfo) {
+    // The lock must not be held while a sink runs
+    let (config, original) = {
+        let installed = installed();
+        let config = installed.configs.last().map(|(_, config)| config.clone());
+        (config, installed.original.clone())
+    };
+    match (config, original) {
+        (Some(config), Some(original)) => config.handle(panic_info, &original),
+        (None, Some(original)) => original(panic_info),
+        _ => {}
+    }
+}
+
+// Asks a yes or no question on the terminal, no is the default
+fn confirm(question: &str) -> bool {
+    eprint!("{}", question);
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer).is_ok()
+        && matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+// What the default hook prints, for panics that are not `UserPanic`s
+fn plain_message(payload: &(dyn Any + Send), location: Option<&Location>) -> String {
+    let message = payload_message(payload);
+    let thread = std::thread::current();
+    let location = location.map_or(String::new(), |l| format!(" at {}", l));
+    format!(
+        "thread '{}' panicked{}:\n{}\n",
+        thread.name().unwrap_or("<unnamed>"),
+        location,
+        message
+    )
+}
+
+// The message of a `panic!`, like the default hook prints it
+fn payload_message(payload: &(dyn Any + Send)) -> &str {
+    match (
+        payload.downcast_ref::<&str>(),
+        payload.downcast_ref::<String>(),
+    ) {
+        (Some(s), _) => s,
+        (_, Some(s)) => s.as_str(),
+        _ => "Box<dyn Any>",
+    }
+}
+
+/// This function is used to set custom panic function
+/// Use this to use the custom hooks and set up the developer message
+///
+/// Same as installing a [`HookConfig`] with only the developer message set.
+pub fn set_hooks(developer: Option<&'static str>) -> HookGuard {
+    let mut config = HookConfig::new();
+    if let Some(dev) = developer {
+        config = config.developer(dev);
+    }
+    config.install()
+}
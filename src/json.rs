@@ -0,0 +1,293 @@
+//! The JSON report printed instead of the text one for log pipelines.
+
+use crate::context::Context;
+use crate::{DefaultTemplate, FixStep, ReportTemplate, UserPanic};
+use std::error::Error;
+use std::panic::Location;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// One JSON object on a single line describing the panic
+pub(crate) fn report(
+    panic: &UserPanic,
+    developer: Option<&str>,
+    location: Option<&Location>,
+    context: &Context,
+    time: SystemTime,
+    backtrace: Option<&str>,
+    crash_report: Option<&Path>,
+) -> String {
+    let mut fields = vec![
+        ("code", number_or_null(panic.code)),
+        ("key", string_or_null(panic.key)),
+        ("message", string(&panic.fill(panic.error_msg))),
+        ("severity", string(panic.severity.name())),
+        ("category", panic.category.map_or("null".into(), string)),
+        ("fixable", panic.fix_instructions.is_some().to_string()),
+        ("exit_code", panic.exit_code().to_string()),
+        (
+            "fix_steps",
+            steps(panic, panic.fix_instructions.unwrap_or_default(), None, 1),
+        ),
+        ("fix_actions", list(panic.fix_actions)),
+    ];
+    let args = panic
+        .args
+        .iter()
+        .map(|(name, value)| format!("{}:{}", string(name), string(value)));
+    fields.push((
+        "args",
+        format!("{{{}}}", args.collect::<Vec<_>>().join(",")),
+    ));
+    let mut sources = Vec::new();
+    let mut error = panic.source.as_deref().map(|e| e as &(dyn Error + 'static));
+    while let Some(e) = error {
+        sources.push(string(&e.to_string()));
+        error = e.source();
+    }
+    fields.push(("sources", format!("[{}]", sources.join(","))));
+    fields.append(&mut situation(developer, location, context, time));
+    fields.push(("backtrace", backtrace.map_or("null".into(), string)));
+    let crash_report = crash_report.map(|p| p.display().to_string());
+    fields.push((
+        "crash_report",
+        crash_report.map_or("null".into(), |p| string(&p)),
+    ));
+    object(fields)
+}
+
+// The object printed for panics that are not a `UserPanic`, with the fields
+// that still make sense so pipelines can parse every line the same way
+pub(crate) fn foreign(
+    message: &str,
+    developer: Option<&str>,
+    location: Option<&Location>,
+    context: &Context,
+    time: SystemTime,
+) -> String {
+    let mut fields = vec![
+        ("code", "null".into()),
+        ("key", "null".into()),
+        ("message", string(message)),
+    ];
+    fields.append(&mut situation(developer, location, context, time));
+    object(fields)
+}
+
+// Where and when the panic happened
+fn situation(
+    developer: Option<&str>,
+    location: Option<&Location>,
+    context: &Context,
+    time: SystemTime,
+) -> Vec<(&'static str, String)> {
+    let location = location.map_or("null".into(), |l| {
+        format!(
+            "{{\"file\":{},\"line\":{},\"column\":{}}}",
+            string(l.file()),
+            l.line(),
+            l.column()
+        )
+    });
+    vec![
+        ("developer", developer.map_or("null".into(), string)),
+        ("location", location),
+        (
+            "thread",
+            context.thread.as_deref().map_or("null".into(), string),
+        ),
+        ("context", list(&context.stack)),
+        ("breadcrumbs", list(&context.breadcrumbs)),
+        ("timestamp", string(&rfc3339(time))),
+    ]
+}
+
+fn object(fields: Vec<(&str, String)>) -> String {
+    let fields: Vec<String> = fields
+        .into_iter()
+        .map(|(name, value)| format!("\"{}\":{}", name, value))
+        .collect();
+    format!("{{{}}}", fields.join(","))
+}
+
+// The instructions with the labels of the text report and their children
+fn steps(panic: &UserPanic, steps: &[FixStep], parent: Option<&str>, depth: usize) -> String {
+    let steps: Vec<String> = steps
+        .iter()
+        .zip(1..)
+        .map(|(step, n)| {
+            let label = DefaultTemplate.step_label(parent, n, depth);
+            format!(
+                "{{\"label\":{},\"text\":{},\"steps\":{}}}",
+                string(&label),
+                string(&panic.fill(step.text)),
+                self::steps(panic, step.children, Some(&label), depth + 1)
+            )
+        })
+        .collect();
+    format!("[{}]", steps.join(","))
+}
+
+fn list(items: &[impl AsRef<str>]) -> String {
+    let items: Vec<String> = items.iter().map(|i| string(i.as_ref())).collect();
+    format!("[{}]", items.join(","))
+}
+
+fn number_or_null(code: u32) -> String {
+    match code {
+        0 => "null".into(),
+        code => code.to_string(),
+    }
+}
+
+fn string_or_null(s: &str) -> String {
+    match s {
+        "" => "null".into(),
+        s => string(s),
+    }
+}
+
+// A JSON string literal
+pub(crate) fn string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out += "\\\"",
+            '\\' => out += "\\\\",
+            '\n' => out += "\\n",
+            '\r' => out += "\\r",
+            '\t' => out += "\\t",
+            c if (c as u32) < 0x20 => out += &format!("\\u{:04x}", c as u32),
+            c => out.push(c),
+        }
+    }
+    out + "\""
+}
+
+// `time` in UTC like `2024-03-09T14:05:00Z`
+pub(crate) fn rfc3339(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let (days, rest) = (secs / 86400, secs % 86400);
+    // Days to a civil date, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days as i64 + 719468;
+    let era = z / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rest / 3600,
+        rest % 3600 / 60,
+        rest % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Severity;
+    use std::time::Duration;
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(rfc3339(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+        let leap = UNIX_EPOCH + Duration::from_secs(951_827_696);
+        assert_eq!(rfc3339(leap), "2000-02-29T12:34:56Z");
+        let later = UNIX_EPOCH + Duration::from_secs(1_709_993_100);
+        assert_eq!(rfc3339(later), "2024-03-09T14:05:00Z");
+    }
+
+    #[test]
+    fn report_fields() {
+        let base = UserPanic {
+            key: "ConfigMissing",
+            code: 3,
+            severity: Severity::Error,
+            category: Some("config"),
+            ..UserPanic::new(
+                "Could not open \"{path}\"",
+                Some(&[FixStep {
+                    text: "Create {path}",
+                    children: &[FixStep {
+                        text: "with\ttabs",
+                        children: &[],
+                    }],
+                }]),
+            )
+        };
+        let err = base
+            .clone()
+            .with_arg("path", "C:\\app.toml")
+            .with_source("denied");
+        let location = Location::caller();
+        let context = Context {
+            thread: Some("main".into()),
+            stack: vec!["loading config".into()],
+            breadcrumbs: vec!["started".into(), "loading config".into()],
+        };
+        let json = report(
+            &err,
+            Some("Mail xyz"),
+            Some(location),
+            &context,
+            UNIX_EPOCH,
+            Some("   3: app::main\n"),
+            Some(Path::new("crash.txt")),
+        );
+        assert_eq!(
+            json,
+            format!(
+                "{{\"code\":3,\"key\":\"ConfigMissing\",\"message\":\"Could not open \\\"C:\\\\app.toml\\\"\",\"severity\":\"error\",\"category\":\"config\",\"fixable\":true,\"exit_code\":2,\
+                 \"fix_steps\":[{{\"label\":\"1\",\"text\":\"Create C:\\\\app.toml\",\"steps\":[{{\"label\":\"1.a\",\"text\":\"with\\ttabs\",\"steps\":[]}}]}}],\"fix_actions\":[],\
+                 \"args\":{{\"path\":\"C:\\\\app.toml\"}},\"sources\":[\"denied\"],\"developer\":\"Mail xyz\",\
+                 \"location\":{{\"file\":\"src/json.rs\",\"line\":{},\"column\":{}}},\"thread\":\"main\",\"context\":[\"loading config\"],\"breadcrumbs\":[\"started\",\"loading config\"],\"timestamp\":\"1970-01-01T00:00:00Z\",\"backtrace\":\"   3: app::main\\n\",\"crash_report\":\"crash.txt\"}}",
+                location.line(),
+                location.column()
+            )
+        );
+        let json = report(
+            &base,
+            None,
+            None,
+            &Context::default(),
+            UNIX_EPOCH,
+            None,
+            None,
+        );
+        assert!(
+            json.contains(
+                "\"developer\":null,\"location\":null,\"thread\":null,\"context\":[],\"breadcrumbs\":[]"
+            ),
+            "{}",
+            json
+        );
+        // Panics made in code have neither a code nor a key
+        let json = report(
+            &UserPanic::new("oops", None),
+            None,
+            None,
+            &Context::default(),
+            UNIX_EPOCH,
+            None,
+            None,
+        );
+        assert!(
+            json.starts_with("{\"code\":null,\"key\":null,\"message\":\"oops\","),
+            "{}",
+            json
+        );
+        assert_eq!(
+            foreign("oops", Some("Mail xyz"), None, &Context::default(), UNIX_EPOCH),
+            "{\"code\":null,\"key\":null,\"message\":\"oops\",\"developer\":\"Mail xyz\",\"location\":null,\
+             \"thread\":null,\"context\":[],\"breadcrumbs\":[],\"timestamp\":\"1970-01-01T00:00:00Z\"}"
+        );
+        assert_eq!(string("\u{1b}"), "\"\\u001b\"");
+    }
+}
@@ -5,6 +5,7 @@
 //! and a Yaml File to generate the custom structs.
 //!
 //! This allows for seperate error messages for seperate error and also allows the user to run some simple fixes (if possible).
+//! Fix instructions can be nested as deep as needed.
 //!
 //! ### Output Example
 //!
@@ -19,10 +20,10 @@
 //!
 //!     1: Try to check your Internet Connection.
 //!
-//! 	2: Check if your API request quota has been exhausted.
-//! 		1.  Instructions on how
-//! 		2.  to check
-//! 		3.  API quota
+//!     2: Check if your API request quota has been exhausted.
+//!         2.a: Instructions on how
+//!         2.b: to check
+//!         2.c: API quota
 //!
 //! If the error still persists
 //! Contact the Developer at xyz@wkl.com
@@ -48,39 +49,350 @@
 //! user-panic = "0.1.0"
 //! ```
 //! and make build.rs file as follows
-//! ```
+//! ```ignore
 //! fn main() {
 //!    println!("cargo:rerun-if-changed=errors.yaml");
 //!    println!("cargo:rerun-if-changed=build.rs");
-//!    userpanic::panic_setup!("errors.yaml"); // Enter the yaml file path here
+//!    user_panic::panic_setup!("errors.yaml"); // Enter the yaml file path here
 //! }
 //! ```
 //! This will create `panic_strucs.rs` file in src directory
+//! If the yaml file has a mistake the build fails and cargo prints a warning
+//! with the line and column of the problem for every error found.
+//!
+//! Error names have to be valid rust identifiers, pass
+//! `user_panic::SetupOptions { normalize_names: true }` after the output path to
+//! turn names like `api-error` into `API_ERROR` instead.
+//!
+//! Next to the consts a `PanicCode` enum is generated with a variant for every error.
+//! It converts into the matching `UserPanic`, can be parsed from the error name
+//! and gives every error a numeric code. Entries without a `code` field are numbered
+//! in the order of the yaml file starting at 1 and skipping the pinned codes, so adding
+//! or moving an entry renumbers the ones after it. Pin the codes that have to stay the
+//! same with `code`.
+//!
 //! This file can be then imported and used with panic_any to display the custom panics
-//! ```
+//! ```ignore
 //! mod panic_structs;
 //!
 //! use std::panic::panic_any;
 //! use crate::panic_structs::API;
 //!
 //! fn main(){
-//!     // This sets the custom hook for panic messages
-//!     userpanic::set_hooks(Some("If the error still persists\nContact the developer at xyz@wkl.com"));
+//!     // This sets the custom hook for panic messages until `_hooks` is dropped
+//!     let _hooks = user_panic::set_hooks(Some("If the error still persists\nContact the developer at xyz@wkl.com"));
 //!     // If None is passed then No developer info/message is shown.
 //!
 //!     panic_any(API);
 //! }
 //! ```
+//! ### Templated messages
+//! Messages and instructions can have `{placeholders}` filled in when the panic happens.
+//! Every placeholder has to be listed under `params`, the entry then becomes a function
+//! taking one argument per param instead of a const.
+//! ```txt
+//! ConfigMissing:
+//!   message: Could not open {path}
+//!   params: [path]
+//!   fix instructions:
+//!       - Create {path} or pass another config file
+//! ```
+//! ```ignore
+//! panic_any(ConfigMissing(path.display()));
+//! ```
+//! ### Translations
+//! `message` and `fix instructions` can be given per locale. The panic hook picks the
+//! locale set with `set_locale` or the one in `LC_ALL`, `LC_MESSAGES` or `LANG`, missing
+//! texts come from the default locale (`en` unless changed with `set_default_locale`).
+//! ```txt
+//! DB:
+//!   message:
+//!     en: The database is corrupted
+//!     de: Die Datenbank ist beschädigt
+//! ```
+//! The texts around the message are translated by registering `Phrases` for a locale.
+//! ```
+//! user_panic::add_phrases("de", user_panic::Phrases {
+//!     header: "Das Programm ist abgestürzt",
+//!     error: "Fehler",
+//!     ..user_panic::Phrases::ENGLISH
+//! });
+//! user_panic::set_locale(Some("de"));
+//! ```
+//! ### Configuring the hook
+//! Everything the hook does is set with a `HookConfig`, `set_hooks` installs one with only
+//! the developer message. Installing returns a `HookGuard` that puts back the previous
+//! hook when dropped, so the hook can be limited to a scope like a test. Call `keep` on
+//! it to leave the hook set for good.
+//! ```ignore
+//! user_panic::HookConfig::new()
+//!     .developer("If the error still persists\nContact the developer at xyz@wkl.com")
+//!     .sink(user_panic::LogSink)
+//!     .color(user_panic::ColorChoice::Never)
+//!     .chain(false) // other panics are sent to the same sinks
+//!     .exit(true) // exit right after reporting instead of unwinding
+//!     .install()
+//!     .keep();
+//! ```
+//! ### Exit codes
+//! With `exit(true)` the process exits with a code that tells what went wrong instead of
+//! the `101` of every panic. Errors with fix instructions exit with `2`, errors without
+//! them with `3`, and an entry can pick its own code between 1 and 255.
+//! ```txt
+//! API:
+//!   message: There was an error during the API request
+//!   exit code: 69
+//! ```
+//! ### Severity and categories
+//! `severity` is `fatal` when left out, `error` or `warning` change the first line of the output
+//! and the level `LogSink` and `SyslogSink` log at. `category` is free text to group reports by.
+//! Both are fields of the JSON output and crash reports, and `LogSink` attaches them to its
+//! records as the `severity` and `category` key-values.
+//! ```txt
+//! DiskAlmostFull:
+//!   message: The disk is almost full
+//!   severity: warning
+//!   category: storage
+//! ```
+//! ### Fix actions
+//! Some fixes the program can apply by itself. An entry names them under `fix action` and the
+//! program registers the code behind every name when it starts.
+//! ```txt
+//! CacheCorrupted:
+//!   message: The cache is corrupted
+//!   fix instructions:
+//!     - Delete the cache directory
+//!   fix action: clear-cache
+//! ```
+//! The hook runs them after the report when asked to, `FixActionMode::Ask` asks the user
+//! first and runs nothing when stdin is not a terminal, `FixActionMode::Always` runs them
+//! without asking, like for a `--yes` flag. How every action went is sent to the sinks.
+//! ```ignore
+//! user_panic::add_fix_action("clear-cache", "Delete the cache directory", || {
+//!     std::fs::remove_dir_all(cache_dir())
+//! });
+//! let mode = match std::env::args().any(|a| a == "--yes") {
+//!     true => user_panic::FixActionMode::Always,
+//!     false => user_panic::FixActionMode::Ask,
+//! };
+//! let _hooks = user_panic::HookConfig::new().fix_actions(mode).install();
+//! ```
+//! ### Interactive menu
+//! An interactive hook asks the user what to do next after the report, as long as stdin and
+//! stderr are terminals. Otherwise the output stays the same.
+//! ```txt
+//! [r]etry  [c]opy the report  [s]ave a crash report  [o]pen the bug tracker  [q]uit
+//! ```
+//! Retrying is offered for panics caught by `user_panic::run`, it calls the closure again.
+//! The copied report is the text of a crash report. Quitting exits with the exit code of
+//! the panic.
+//! ```ignore
+//! let _hooks = user_panic::HookConfig::new()
+//!     .interactive(true)
+//!     .bug_tracker("https://github.com/me/app/issues/new")
+//!     .install();
+//! user_panic::run(|| sync());
+//! ```
+//! ### Bug reports
+//! Errors without fix instructions ask the user to submit a bug report. With a bug tracker
+//! the hook prints a link to a new issue that is already filled in. The `{title}`, `{body}`,
+//! `{key}`, `{message}`, `{version}` and `{os}` placeholders of the url are replaced by the
+//! percent-encoded values of the panic, the body also has the start of the backtrace.
+//! ```ignore
+//! let _hooks = user_panic::HookConfig::new()
+//!     .bug_tracker(
+//!         user_panic::BugTracker::new("https://github.com/me/app/issues/new?title={title}&body={body}")
+//!             .version(env!("CARGO_PKG_VERSION")),
+//!     )
+//!     .install();
+//! ```
+//! ### Running main
+//! `user_panic::run` reports the user panics raised in a closure and exits with their code,
+//! whether `exit(true)` is set or not. The closure can also return a `Result` whose error
+//! converts into a `UserPanic`, like the `PanicCode` enum generated from the yaml file or
+//! an error type of the program. Other panics unwind as usual.
+//! ```ignore
+//! enum AppError {
+//!     Config(std::io::Error),
+//! }
+//! impl From<AppError> for UserPanic {
+//!     fn from(e: AppError) -> UserPanic {
+//!         match e {
+//!             AppError::Config(e) => ConfigMissing("app.toml").with_source(e),
+//!         }
+//!     }
+//! }
+//!
+//! fn main() {
+//!     user_panic::run(|| {
+//!         let config = std::fs::read_to_string("app.toml").map_err(AppError::Config)?;
+//!         start(&config);
+//!         Ok::<_, AppError>(())
+//!     });
+//! }
+//! ```
+//! ### Output sinks
+//! The output goes to stderr unless other `PanicSink`s are added, there are sinks for any
+//! `Write`, files, the `log` crate and syslog. Every sink gets its own rendering of the
+//! output, so only the terminal gets colors.
+//! ```ignore
+//! user_panic::HookConfig::new()
+//!     .sink(user_panic::StderrSink)
+//!     .sink(user_panic::FileSink::new("panics.log"))
+//!     .sink(user_panic::SyslogSink::new())
+//!     .install()
+//!     .keep();
+//! ```
+//! ### Colors
+//! When stderr is a terminal the hook styles the error line, the instruction numbers and
+//! the developer message and wraps the text to `COLUMNS`, or 80 columns when it is not set.
+//! Piped output stays plain text, setting `NO_COLOR` turns the colors off. `HookConfig::color`
+//! overrides both.
+//! ### JSON output
+//! For CI and log pipelines the hook can print every panic as a single line JSON object
+//! with the code, key, message, fix steps, developer message, location, thread name and
+//! timestamp. Select it with `HookConfig::format(Format::Json)` or by setting
+//! `USER_PANIC_FORMAT=json`, which takes precedence over the code.
+//! A code or key that a panic doesn't have is `null`. Other panics are printed as objects
+//! with a `null` code and key instead of going to the previous hook, and panics without a
+//! message print nothing, so every line is an object.
+//! ### Crash reports
+//! The hook can also save a crash report for every panic, with the message, fix steps,
+//! location, backtrace, program version, platform and command line arguments. The output
+//! then ends with the path of the report so users can attach it to their bug report.
+//! ```ignore
+//! user_panic::HookConfig::new()
+//!     .crash_reports(user_panic::CrashReports::in_state_dir().version(env!("CARGO_PKG_VERSION")))
+//!     .install()
+//!     .keep();
+//! ```
+//! ### Custom layout
+//! The report is put together by a `ReportTemplate` with one method per section: header,
+//! error, fix intro, step and footer. Each has a default giving the output above, a template
+//! overrides only what it needs and is passed to `HookConfig::template`.
+//! ```ignore
+//! struct Plain;
+//! impl user_panic::ReportTemplate for Plain {
+//!     fn header(&self, _: &user_panic::ReportContext) -> String {
+//!         String::new()
+//!     }
+//! }
+//! user_panic::HookConfig::new().template(Plain).install().keep();
+//! ```
+//! ### Technical details
+//! The error that caused a panic can be attached to it. It is only shown to the user
+//! after switching the hook to `Verbosity::Technical`, which prints the whole chain of
+//! `Error::source`s below the message. A backtrace without the frames of the standard
+//! library and of this crate is printed as well when `RUST_BACKTRACE` is set, it is also
+//! part of the JSON output and always saved in crash reports. `HookConfig::backtraces`
+//! changes when one is captured.
+//! ```ignore
+//! user_panic::HookConfig::new().verbosity(user_panic::Verbosity::Technical).install().keep();
+//! let body = reqwest::blocking::get(url).unwrap_or_else(|e| panic_any(API.with_source(e)));
+//! ```
+//! ### Context
+//! Every thread keeps a stack of the operations in flight and a trail of the last 20
+//! breadcrumbs. They are reported with the thread name in the JSON output, in crash
+//! reports and at `Verbosity::Technical`, so support knows what the program was doing.
+//! ```ignore
+//! fn load_config(path: &Path) -> Config {
+//!     let _context = user_panic::context!("loading config {}", path.display());
+//!     user_panic::breadcrumb("reading the file");
+//!     // ...
+//! }
+//! ```
+//! Async tasks move between threads and share them with other tasks, wrap them in
+//! `user_panic::task_context` to give every task a stack and trail of its own.
+//! ```ignore
+//! tokio::spawn(user_panic::task_context(async move {
+//!     let _context = user_panic::context!("syncing account {}", id);
+//!     sync(id).await
+//! }));
+//! ```
+//! ### Without writing into src
+//! With the `macros` feature the yaml file can be embedded at compile time instead,
+//! errors in the file are then reported as compile errors.
+//! ```toml
+//! [dependencies]
+//! user-panic = { version = "0.1.0", features = ["macros"] }
+//! ```
+//! ```ignore
+//! mod panic_structs {
+//!     user_panic::include_panics!("errors.yaml");
+//! }
+//! ```
+//! ### Without a build script
+//! The same yaml file can also be loaded when the program runs with a `PanicRegistry`,
+//! which can be built in code as well.
+//! ```no_run
+//! user_panic::set_hooks(None).keep();
+//! user_panic::PanicRegistry::from_path("errors.yaml").unwrap().install();
+//!
+//! user_panic::raise("API");
+//! ```
+
+mod action;
+mod backtrace;
+mod context;
+mod crash;
+mod hook;
+mod json;
+mod locale;
+mod prompt;
+mod registry;
+mod run;
+mod sink;
+mod template;
+mod terminal;
+mod tracker;
+
+// The yaml parsing and code generation are shared with `include_panics!`
+use user_panic_codegen::{codegen, schema};
+
+pub use action::{add_fix_action, FixActionMode};
+pub use backtrace::BacktraceMode;
+pub use codegen::SetupOptions;
+pub use context::{breadcrumb, context, task_context, ContextGuard, TaskContext};
+pub use crash::CrashReports;
+pub use hook::{set_hooks, ColorChoice, Format, HookConfig, HookGuard, Verbosity};
+pub use locale::{add_phrases, locale, set_default_locale, set_locale, Phrases};
+pub use registry::{raise, LoadError, PanicRegistry, PanicRegistryBuilder};
+pub use run::{run, RunOutput};
+pub use schema::{Instruction, SchemaError, SchemaErrorKind, Severity};
+#[cfg(unix)]
+pub use sink::SyslogSink;
+pub use sink::{FileSink, LogSink, PanicSink, StderrSink, WriteSink};
+pub use template::{DefaultTemplate, ReportContext, ReportTemplate, StepContext};
+pub use terminal::Terminal;
+pub use tracker::BugTracker;
+#[cfg(feature = "macros")]
+pub use user_panic_macros::include_panics;
 
-use log::{debug, info};
+use std::borrow::Cow;
+use std::error::Error;
 use std::fmt;
 use std::io::Write;
-use std::panic;
-use std::panic::PanicInfo;
-use yaml_rust::{Yaml, YamlLoader};
+use std::sync::Arc;
 
-type StrList = [&'static [&'static str]];
-type Panicfn = Box<dyn Fn(&PanicInfo) + Sync + Send>;
+#[derive(Debug, Clone, Copy)]
+/// A single fix instruction with its own sub instructions
+///
+/// The instructions are numbered `1`, `1.a`, `1.a.i` and so on for deeper levels
+pub struct FixStep {
+    /// The instruction itself
+    pub text: &'static str,
+    /// Instructions to follow to complete this one, can be empty
+    pub children: &'static [FixStep],
+}
+
+#[derive(Debug, Clone, Copy)]
+/// The message and fix instructions of a [`UserPanic`] in another language
+pub struct Translation {
+    /// Locale of the texts like `de` or `pt_BR`
+    pub locale: &'static str,
+    pub error_msg: &'static str,
+    pub fix_instructions: Option<&'static [FixStep]>,
+}
 
 #[derive(Debug, Clone)]
 /// This Struct is auto generated from the yaml file
@@ -90,150 +402,246 @@ pub struct UserPanic {
     /// If left empty then the program panics silently without giving any output
     pub error_msg: &'static str,
     /// It contains the instructions to fix the error
-    pub fix_instructions: Option<&'static StrList>,
+    pub fix_instructions: Option<&'static [FixStep]>,
+    /// Name of the error in the yaml file
+    pub key: &'static str,
+    /// Numeric code of the error, `0` if it has none
+    pub code: u32,
+    /// Values for the `{name}` placeholders in the message and instructions
+    pub args: Vec<(&'static str, String)>,
+    /// The error that caused the panic, shown as technical details
+    /// when the [`Verbosity`] allows it
+    pub source: Option<Arc<dyn Error + Send + Sync>>,
+    /// The texts for other locales, the panic hook picks one with [`locale`]
+    pub translations: &'static [Translation],
+    /// Exit code of the process, see [`UserPanic::exit_code`]
+    pub exit_code: Option<i32>,
+    /// Names of the fixes the program can apply by itself, see [`add_fix_action`]
+    pub fix_actions: &'static [&'static str],
+    /// How bad the error is
+    pub severity: Severity,
+    /// What the error is about, like `network` or `config`, for grouping reports
+    pub category: Option<&'static str>,
 }
-impl fmt::Display for UserPanic {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        if self.error_msg == "" {
-            return write!(f, "");
+impl UserPanic {
+    /// Exit code of errors with fix instructions that don't set one
+    pub const FIXABLE_EXIT_CODE: i32 = 2;
+    /// Exit code of errors without fix instructions that don't set one
+    pub const UNFIXABLE_EXIT_CODE: i32 = 3;
+
+    /// A panic with only a message and fix instructions, the other fields
+    /// are empty
+    ///
+    /// Panics built by hand should start from it so they keep compiling when
+    /// fields are added. Struct update syntax with it only compiles outside
+    /// of consts.
+    ///
+    /// ```
+    /// use user_panic::{FixStep, UserPanic};
+    ///
+    /// let disk_full = UserPanic {
+    ///     key: "DiskFull",
+    ///     ..UserPanic::new(
+    ///         "The disk is full",
+    ///         Some(&[FixStep { text: "Free some space", children: &[] }]),
+    ///     )
+    /// };
+    /// ```
+    pub const fn new(
+        error_msg: &'static str,
+        fix_instructions: Option<&'static [FixStep]>,
+    ) -> Self {
+        UserPanic {
+            error_msg,
+            fix_instructions,
+            key: "",
+            code: 0,
+            args: Vec::new(),
+            source: None,
+            translations: &[],
+            exit_code: None,
+            fix_actions: &[],
+            severity: Severity::Fatal,
+            category: None,
         }
-        // Need something better than "The Program Crashed" :(
-        let mut s = String::from("The Program Crashed\n\n");
-        if self.fix_instructions.is_none() {
-            s += &format!("Error: {}", self.error_msg);
-            s += "\nIt seems like an error that can't be fixed by you!\nPlease submit a Bug report to Developer\n";
-        } else {
-            s += &format!("Error: {}", self.error_msg);
-            s += "\nIt seems like an error that can be fixed by you!\nPlease follow the following instructions to try and fix the Error\n";
-            let insts = self.fix_instructions.as_ref().unwrap();
-            let mut i = 1;
-            for inst in *insts {
-                s += &format!("\n\t{}: {}\n", i, inst[0]);
-                let inst = &inst[1..];
-                if inst.len() > 1 {
-                    let mut j = 1;
-                    for ii in inst {
-                        s += &format!("\t\t{}. {}\n", j, ii);
-                        j += 1;
-                    }
-                }
-                i += 1;
+    }
+
+    /// Attaches the error that caused the panic
+    ///
+    /// ```no_run
+    /// # let registry = user_panic::PanicRegistry::builder().unfixable("API", "").build();
+    /// # let API = registry.get("API").unwrap().clone();
+    /// # fn request() -> std::io::Result<()> { Ok(()) }
+    /// if let Err(e) = request() {
+    ///     std::panic::panic_any(API.clone().with_source(e));
+    /// }
+    /// ```
+    pub fn with_source(mut self, source: impl Into<Box<dyn Error + Send + Sync>>) -> Self {
+        self.source = Some(Arc::from(source.into()));
+        self
+    }
+    /// The source error and the errors that caused it, one per line
+    ///
+    /// `None` if no source error is attached
+    pub fn technical_details(&self) -> Option<String> {
+        self.details(Phrases::ENGLISH.technical_details)
+    }
+    fn details(&self, heading: &str) -> Option<String> {
+        let mut error: &(dyn Error + 'static) = self.source.as_deref()?;
+        let mut s = format!("{}:\n", heading);
+        for i in 0.. {
+            s += &format!("\t{}: {}\n", i, error);
+            match error.source() {
+                Some(source) => error = source,
+                None => break,
             }
         }
-        write!(f, "{}", s)
+        Some(s)
     }
-}
-/// This function is used to set custom panic function
-/// Use this to use the custom hooks and set up the developer message
-pub fn set_hooks(developer: Option<&'static str>) {
-    let org: Panicfn = panic::take_hook();
-    if let Some(dev) = developer {
-        // Used if The developer provides custom info
-        panic::set_hook(Box::new(move |pan_inf| {
-            panic_func(pan_inf, &org);
-            eprintln!("{}", dev);
-        }))
-    } else {
-        // Used if Developer doesn't want info to be shown.
-        panic::set_hook(Box::new(move |pan_inf| {
-            panic_func(pan_inf, &org);
-        }));
+    /// Sets the exit code of the process, like `exit code` in the yaml file
+    pub fn with_exit_code(mut self, code: i32) -> Self {
+        self.exit_code = Some(code);
+        self
     }
-}
-// The panic function
-fn panic_func(panic_info: &PanicInfo, original: &Panicfn) {
-    match panic_info.payload().downcast_ref::<UserPanic>() {
-        Some(err) => {
-            if err.error_msg != "" {
-                eprintln!("{}", err);
+    /// The code the process exits with when the hook is set to
+    /// [`exit`](HookConfig::exit)
+    ///
+    /// Defaults to [`FIXABLE_EXIT_CODE`](Self::FIXABLE_EXIT_CODE) or
+    /// [`UNFIXABLE_EXIT_CODE`](Self::UNFIXABLE_EXIT_CODE), both apart from
+    /// the `101` of regular panics.
+    pub fn exit_code(&self) -> i32 {
+        match (self.exit_code, self.fix_instructions) {
+            (Some(code), _) => code,
+            (None, Some(_)) => Self::FIXABLE_EXIT_CODE,
+            (None, None) => Self::UNFIXABLE_EXIT_CODE,
+        }
+    }
+    /// Sets the severity, like `severity` in the yaml file
+    pub fn with_severity(mut self, severity: Severity) -> Self {
+        self.severity = severity;
+        self
+    }
+    /// Sets the category, like `category` in the yaml file
+    pub fn with_category(mut self, category: &'static str) -> Self {
+        self.category = Some(category);
+        self
+    }
+    /// Sets the value of a `{name}` placeholder
+    ///
+    /// Panics generated from entries with `params` get these from their
+    /// constructor function, this is for panics loaded into a [`PanicRegistry`]
+    pub fn with_arg(mut self, name: &'static str, value: impl ToString) -> Self {
+        self.args.retain(|(n, _)| *n != name);
+        self.args.push((name, value.to_string()));
+        self
+    }
+    // Replaces the placeholders that have a value, the rest is left as it is
+    fn fill<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let mut text = Cow::Borrowed(text);
+        for (name, value) in &self.args {
+            let placeholder = format!("{{{}}}", name);
+            if text.contains(&placeholder) {
+                text = Cow::Owned(text.replace(&placeholder, value));
             }
         }
-        // Default to original panic routine if downcast_ref fails
-        None => original(panic_info),
+        text
     }
 }
-// Returns the auto generated rust code
-fn read_from_yml(yaml: String) -> String {
-    debug!("Started Reading the yaml string");
-    let mut file = "use user_panic::UserPanic;\n".to_string();
-    let yaml = YamlLoader::load_from_str(&yaml).unwrap();
-    let structs = &yaml[0];
-    if let Yaml::Hash(hash) = structs {
-        info!("Found Hash");
-        // for test case keys -> foo bar
-        for (key, val) in hash {
-            let st_name = key.as_str().unwrap();
-            debug!("parsing key {}", st_name);
-            file += &format!(
-                "pub const {}:UserPanic = UserPanic {{{}}};",
-                st_name,
-                get_err_msg(val)
-            );
-        }
+impl fmt::Display for UserPanic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.render(&DefaultTemplate, &[], Terminal::PLAIN))
     }
-    file
 }
-// Helper function for read_from_yml
-// Idk why I named it this it doesn't make sense
-fn get_err_msg(hash: &Yaml) -> String {
-    let print_arr = |arr: &Vec<Yaml>| -> String {
-        let mut s = String::new();
-        let _ = arr
-            .iter()
-            .map(|a| {
-                s += &format!(",\"{}\"", a.as_str().unwrap());
-            })
-            .collect::<Vec<_>>();
+impl UserPanic {
+    /// The output in `locale`, falling back to the default locale
+    ///
+    /// [`Display`](fmt::Display) always uses the untranslated texts.
+    pub fn display_in(&self, locale: &str) -> String {
+        self.render_with(&DefaultTemplate, Some(locale), Terminal::PLAIN)
+    }
+    /// The output laid out by `template` for `terminal`, in `locale` if given
+    pub fn render_with(
+        &self,
+        template: &dyn ReportTemplate,
+        locale: Option<&str>,
+        terminal: Terminal,
+    ) -> String {
+        match locale {
+            Some(locale) => self.render(template, &locale::candidates(Some(locale)), terminal),
+            None => self.render(template, &[], terminal),
+        }
+    }
+    // The output in the first of the candidate locales that has a translation
+    fn render(
+        &self,
+        template: &dyn ReportTemplate,
+        candidates: &[String],
+        terminal: Terminal,
+    ) -> String {
+        if self.error_msg.is_empty() {
+            return String::new();
+        }
+        let translations = self.translations.iter().map(|t| (t.locale, t));
+        let (error_msg, fix_instructions) = match locale::find(candidates, translations) {
+            Some(t) => (t.error_msg, t.fix_instructions),
+            None => (self.error_msg, self.fix_instructions),
+        };
+        let phrases = locale::phrases(candidates);
+        let report = ReportContext {
+            panic: self,
+            phrases: &phrases,
+            message: &self.fill(error_msg),
+            fixable: fix_instructions.is_some(),
+            terminal,
+        };
+        let mut s = template.header(&report);
+        s += &template.error(&report);
+        s += &template.fix_intro(&report);
+        for (inst, n) in fix_instructions.unwrap_or_default().iter().zip(1..) {
+            let label = template.step_label(None, n, 1);
+            self.write_step(template, &report, &mut s, inst, &label, 1);
+        }
         s
-    };
-    let mut s = String::new();
-    debug!("found hash {:#?}", hash);
-    let err_ms = hash["message"].as_str().unwrap();
-    debug!("Collecting  err message: {}", err_ms);
-    debug!("{:?}", &hash["fix instructions"]);
-    if let Yaml::Array(arr) = &hash["fix instructions"] {
-        debug!("Found fix instructions");
-        s += &format!("error_msg:\"{}\",fix_instructions:Some(&[", err_ms);
-        let items = arr.len();
-        debug!("Number of instuctions {}", items);
-        let mut i = 0;
-        while i < items {
-            if i + 1 < items {
-                match &arr[i + 1] {
-                    Yaml::String(_) => {
-                        s += &format!("&[\"{}\"],", arr[i].as_str().unwrap());
-                        i += 1;
-                    }
-                    Yaml::Array(ar) => {
-                        s += &format!("&[\"{}\"{}],", arr[i].as_str().unwrap(), print_arr(ar));
-                        i += 2;
-                    }
-                    _ => {}
-                }
-            } else {
-                match &arr[i] {
-                    Yaml::String(ss) => {
-                        s += &format!("&[\"{}\"],", ss);
-                        i += 1;
-                    }
-                    Yaml::Array(ar) => {
-                        s += &format!("&[\"{}\"{}],", arr[i].as_str().unwrap(), print_arr(ar));
-                        i += 2;
-                    }
-                    _ => {}
-                }
-            }
+    }
+    // Writes a step followed by its children
+    fn write_step(
+        &self,
+        template: &dyn ReportTemplate,
+        report: &ReportContext,
+        s: &mut String,
+        step: &FixStep,
+        label: &str,
+        depth: usize,
+    ) {
+        let text = self.fill(step.text);
+        *s += &template.step(
+            report,
+            &StepContext {
+                label,
+                depth,
+                text: &text,
+            },
+        );
+        for (child, n) in step.children.iter().zip(1..) {
+            let label = template.step_label(Some(label), n, depth + 1);
+            self.write_step(template, report, s, child, &label, depth + 1);
         }
-        s += "]),";
-    } else {
-        s += &format!("error_msg:\"{}\",fix_instructions: None,", err_ms);
     }
-    s
 }
+/// Error returned when parsing a generated `PanicCode` from an unknown name or code
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownPanicCode(pub String);
+impl fmt::Display for UnknownPanicCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unknown panic `{}`", self.0)
+    }
+}
+impl std::error::Error for UnknownPanicCode {}
 
 #[macro_export]
 /// Macro to be used in build script
-/// Only yaml file path or both yaml and output rust file can be provided
+/// Only yaml file path or both yaml and output rust file can be provided,
+/// [`SetupOptions`] can be passed after the output file
 macro_rules! panic_setup {
     ($file_path:expr) => {
         user_panic::panic_setup_function($file_path, "src/panic_structs.rs");
@@ -241,12 +649,31 @@ macro_rules! panic_setup {
     ($file_path:expr,$file_out:expr) => {
         user_panic::panic_setup_function($file_path, $file_out);
     };
+    ($file_path:expr,$file_out:expr,$options:expr) => {
+        user_panic::panic_setup_function_with($file_path, $file_out, &$options);
+    };
 }
 /// Not intended to be used directly and to be called by panic_setup! macro
 /// The main build script function
+///
+/// Problems in the yaml file are printed as `cargo:warning` lines
+/// pointing at the offending line and the build script exits with an error.
 pub fn panic_setup_function(path_from: &str, path_to: &str) {
+    panic_setup_function_with(path_from, path_to, &SetupOptions::default());
+}
+/// Same as [`panic_setup_function`] with custom [`SetupOptions`]
+pub fn panic_setup_function_with(path_from: &str, path_to: &str, options: &SetupOptions) {
     let file_str = std::fs::read_to_string(path_from).expect("Failed to read yaml file");
-    let s = read_from_yml(file_str);
+    let s = match codegen::read_from_yml(file_str, options) {
+        Ok(s) => s,
+        Err(errors) => {
+            for e in &errors {
+                println!("cargo:warning={}:{}", path_from, e);
+            }
+            eprintln!("{} has {} error(s)", path_from, errors.len());
+            std::process::exit(1);
+        }
+    };
     let mut fp = std::fs::File::create(path_to).expect("failed to create output file");
     write!(&mut fp, "{}", s).expect("failed to write to file");
 }
@@ -257,59 +684,276 @@ mod tests {
     #[test]
     #[should_panic]
     fn it_works() {
-        const ERROR: UserPanic = UserPanic {
-            error_msg: "This is an error",
-            fix_instructions: Some(&[
-                &["Only one"],
-                &["one", "two", "tem"],
-                &["bem", "lem", "jem"],
+        const ERROR: UserPanic = UserPanic::new(
+            "This is an error",
+            Some(&[
+                FixStep {
+                    text: "Only one",
+                    children: &[],
+                },
+                FixStep {
+                    text: "one",
+                    children: &[
+                        FixStep {
+                            text: "two",
+                            children: &[],
+                        },
+                        FixStep {
+                            text: "tem",
+                            children: &[],
+                        },
+                    ],
+                },
             ]),
-        };
+        );
 
-        set_hooks(None);
+        let _hooks = set_hooks(None);
         std::panic::panic_any(ERROR);
     }
 
     #[test]
-    fn print_s() {
-        //        env_logger::init();
-        let s = "
-foo:
-    message: this is the main error
-    fix instructions:
-        - first
-        - - in first
-          - in first second
-        - second
-        - - second first
-          - second second
-        - third
-bar:
-    message: This is un fixable error
-";
-        let s = read_from_yml(s.to_string());
-        assert_eq!("use user_panic::UserPanic;\npub const foo:UserPanic = UserPanic {error_msg:\"this is the main error\",fix_instructions:Some(&[&[\"first\",\"in first\",\"in first second\"],&[\"second\",\"second first\",\"second second\"],&[\"third\"],]),};pub const bar:UserPanic = UserPanic {error_msg:\"This is un fixable error\",fix_instructions: None,};", s);
+    fn output_string_fixable() {
+        const fn step(text: &'static str, children: &'static [FixStep]) -> FixStep {
+            FixStep { text, children }
+        }
+        const ERR: UserPanic = UserPanic::new(
+            "Error msg",
+            Some(&[
+                step("One", &[]),
+                step("two", &[step("two-one", &[]), step("two-two", &[])]),
+                step("Three", &[step("three-one", &[])]),
+            ]),
+        );
+        let s = format!("{}", ERR);
+        let manual = "The Program Crashed\n\nError: Error msg\nIt seems like an error that can be fixed by you!\nPlease follow the following instructions to try and fix the Error\n\n\t1: One\n\n\t2: two\n\t\t2.a: two-one\n\t\t2.b: two-two\n\n\t3: Three\n\t\t3.a: three-one\n";
+        assert_eq!(s, manual);
     }
 
     #[test]
-    fn output_string_fixable() {
-        const ERR: UserPanic = UserPanic {
-            error_msg: "Error msg",
-            fix_instructions: Some(&[&["One"], &["two", "two-one", "two-two"], &["Three"]]),
-        };
+    fn output_string_nested() {
+        const fn step(text: &'static str, children: &'static [FixStep]) -> FixStep {
+            FixStep { text, children }
+        }
+        const ERR: UserPanic = UserPanic::new(
+            "Deep",
+            Some(&[step(
+                "top",
+                &[step(
+                    "a",
+                    &[
+                        step("i", &[]),
+                        step("ii", &[step("1", &[])]),
+                        step("iii", &[]),
+                        step("iv", &[]),
+                    ],
+                )],
+            )]),
+        );
         let s = format!("{}", ERR);
-        let manual = "The Program Crashed\n\nError: Error msg\nIt seems like an error that can be fixed by you!\nPlease follow the following instructions to try and fix the Error\n\n\t1: One\n\n\t2: two\n\t\t1. two-one\n\t\t2. two-two\n\n\t3: Three\n";
-        assert_eq!(s, manual);
+        assert!(s.ends_with("\n\t1: top\n\t\t1.a: a\n\t\t\t1.a.i: i\n\t\t\t1.a.ii: ii\n\t\t\t\t1.a.ii.1: 1\n\t\t\t1.a.iii: iii\n\t\t\t1.a.iv: iv\n"), "{}", s);
+        assert_eq!(template::step_number(27, 2), "aa");
+        assert_eq!(template::step_number(1994, 3), "mcmxciv");
     }
 
     #[test]
-    fn output_string_unfixable() {
-        const ERR: UserPanic = UserPanic {
-            error_msg: "Unfixable Error",
-            fix_instructions: None,
+    fn output_string_args() {
+        const ERR: UserPanic = UserPanic::new(
+            "Could not open {path} {path} {url}",
+            Some(&[FixStep {
+                text: "Check {path} and {not_set}",
+                children: &[],
+            }]),
+        );
+        let s = format!("{}", ERR.with_arg("path", "a.txt").with_arg("url", 1));
+        assert!(s.contains("Error: Could not open a.txt a.txt 1\n"), "{}", s);
+        assert!(s.contains("\t1: Check a.txt and {not_set}\n"), "{}", s);
+        let s = format!("{}", ERR.with_arg("path", "a").with_arg("path", "b"));
+        assert!(s.contains("Error: Could not open b b {url}\n"), "{}", s);
+    }
+
+    #[test]
+    fn technical_details() {
+        #[derive(Debug)]
+        struct Outer(std::io::Error);
+        impl fmt::Display for Outer {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "request failed")
+            }
+        }
+        impl Error for Outer {
+            fn source(&self) -> Option<&(dyn Error + 'static)> {
+                Some(&self.0)
+            }
+        }
+        const ERR: UserPanic = UserPanic::new("API error", None);
+        assert_eq!(ERR.technical_details(), None);
+        let io = std::io::Error::new(std::io::ErrorKind::TimedOut, "timed out");
+        let err = ERR.with_source(Outer(io));
+        assert_eq!(
+            err.technical_details().unwrap(),
+            "Technical details:\n\t0: request failed\n\t1: timed out\n"
+        );
+        // The friendly message stays the same
+        assert_eq!(err.to_string(), ERR.to_string());
+        let err = ERR.with_source("just a string");
+        assert_eq!(
+            err.technical_details().unwrap(),
+            "Technical details:\n\t0: just a string\n"
+        );
+    }
+
+    #[test]
+    fn output_string_translated() {
+        let err = UserPanic {
+            translations: &[
+                Translation {
+                    locale: "de",
+                    error_msg: "{what} ist kaputt",
+                    fix_instructions: Some(&[FixStep {
+                        text: "Neu starten",
+                        children: &[],
+                    }]),
+                },
+                Translation {
+                    locale: "en",
+                    error_msg: "Broken {what}",
+                    fix_instructions: None,
+                },
+            ],
+            ..UserPanic::new("Broken {what}", None)
+        };
+        add_phrases(
+            "de-AT",
+            Phrases {
+                header: "Das Programm ist abgestürzt",
+                error: "Fehler",
+                ..Phrases::ENGLISH
+            },
+        );
+        let err = err.with_arg("what", "DB");
+        let s = err.display_in("de_AT.UTF-8");
+        assert!(
+            s.starts_with("Das Programm ist abgestürzt\n\nFehler: DB ist kaputt\n"),
+            "{}",
+            s
+        );
+        assert!(s.ends_with("\n\t1: Neu starten\n"), "{}", s);
+        let s = err.display_in("de_CH");
+        assert!(
+            s.starts_with("The Program Crashed\n\nError: DB ist kaputt\n"),
+            "{}",
+            s
+        );
+        assert_eq!(err.display_in("ja"), err.to_string());
+        assert!(err.to_string().contains("Error: Broken DB\n"));
+    }
+
+    #[test]
+    fn output_string_template() {
+        struct Markdown;
+        impl ReportTemplate for Markdown {
+            fn header(&self, report: &ReportContext) -> String {
+                format!("# {} ({})\n", report.phrases.header, report.panic.key)
+            }
+            fn fix_intro(&self, _: &ReportContext) -> String {
+                String::new()
+            }
+            fn step(&self, _: &ReportContext, step: &StepContext) -> String {
+                format!(
+                    "{}- {} {}\n",
+                    "  ".repeat(step.depth - 1),
+                    step.label,
+                    step.text
+                )
+            }
+            fn step_label(&self, _: Option<&str>, number: usize, _: usize) -> String {
+                format!("{})", number)
+            }
+        }
+        let err = UserPanic {
+            key: "DB",
+            ..UserPanic::new(
+                "Broken {what}",
+                Some(&[FixStep {
+                    text: "Fix {what}",
+                    children: &[FixStep {
+                        text: "now",
+                        children: &[],
+                    }],
+                }]),
+            )
         };
+        let s = err
+            .clone()
+            .with_arg("what", "db")
+            .render_with(&Markdown, None, Terminal::PLAIN);
+        assert_eq!(
+            s,
+            "# The Program Crashed (DB)\nError: Broken db\n- 1) Fix db\n  - 1) now\n"
+        );
+        assert_eq!(
+            err.render_with(&DefaultTemplate, None, Terminal::PLAIN),
+            err.to_string()
+        );
+        assert_eq!(
+            Markdown.footer(Some("Mail me"), &Terminal::PLAIN),
+            "Mail me\n"
+        );
+    }
+
+    #[test]
+    fn output_string_terminal() {
+        const ERR: UserPanic = UserPanic::new(
+            "There was an error during the API request",
+            Some(&[FixStep {
+                text: "Check if your API request quota has been exhausted.",
+                children: &[FixStep {
+                    text: "Instructions on how",
+                    children: &[],
+                }],
+            }]),
+        );
+        let terminal = Terminal {
+            color: false,
+            width: Some(40),
+        };
+        let s = ERR.render_with(&DefaultTemplate, None, terminal);
+        assert!(
+            s.contains("Error: There was an error during the API\n       request\n"),
+            "{}",
+            s
+        );
+        assert!(s.contains("\n    1: Check if your API request quota\n       has been exhausted.\n        1.a: Instructions on how\n"), "{}", s);
+        let terminal = Terminal {
+            color: true,
+            width: None,
+        };
+        let s = ERR.render_with(&DefaultTemplate, None, terminal);
+        assert!(
+            s.starts_with(
+                "\x1b[1;31mThe Program Crashed\x1b[0m\n\n\x1b[1;31mError:\x1b[0m \x1b[1mThere"
+            ),
+            "{:?}",
+            s
+        );
+        assert!(s.contains("\t\x1b[1;36m1\x1b[0m: Check"), "{:?}", s);
+    }
+
+    #[test]
+    fn output_string_unfixable() {
+        const ERR: UserPanic = UserPanic::new("Unfixable Error", None);
         let s = format!("{}", ERR);
         let manual = "The Program Crashed\n\nError: Unfixable Error\nIt seems like an error that can't be fixed by you!\nPlease submit a Bug report to Developer\n";
         assert_eq!(s, manual);
+        assert_eq!(ERR.exit_code(), UserPanic::UNFIXABLE_EXIT_CODE);
+        assert_eq!(ERR.with_exit_code(7).exit_code(), 7);
+        let s = ERR.with_severity(Severity::Warning).to_string();
+        assert!(
+            s.starts_with("The Program Needs Your Attention\n\n"),
+            "{}",
+            s
+        );
+        let s = ERR.with_severity(Severity::Error).to_string();
+        assert!(s.starts_with("The Program Ran Into an Error\n\n"), "{}", s);
     }
 }
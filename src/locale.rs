@@ -0,0 +1,209 @@
+//! Choosing the language of the panic output.
+//!
+//! The locale set with [`set_locale`] wins, otherwise it is read from the
+//! `LC_ALL`, `LC_MESSAGES` and `LANG` environment variables like other
+//! programs do. Texts missing in that locale come from the default locale
+//! and then from the untranslated texts.
+
+use crate::Severity;
+use std::sync::RwLock;
+
+static LOCALE: RwLock<Option<String>> = RwLock::new(None);
+static DEFAULT_LOCALE: RwLock<Option<String>> = RwLock::new(None);
+// Phrases registered on top of the built in english ones
+static PHRASES: RwLock<Vec<(String, Phrases)>> = RwLock::new(Vec::new());
+
+/// The fixed texts around the message of a panic
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Phrases {
+    /// First line of the output of [`Severity::Fatal`] errors
+    pub header: &'static str,
+    /// First line of the output of [`Severity::Error`] errors
+    pub error_header: &'static str,
+    /// First line of the output of [`Severity::Warning`] errors
+    pub warning_header: &'static str,
+    /// Put in front of the message
+    pub error: &'static str,
+    /// Shown below the message of errors with fix instructions
+    pub fixable: &'static str,
+    /// Shown below the message of errors without fix instructions
+    pub unfixable: &'static str,
+    /// Heading of the source errors, see [`Verbosity`](crate::Verbosity)
+    pub technical_details: &'static str,
+    /// Put in front of the path of a saved crash report
+    pub report_saved: &'static str,
+    /// Put in front of the bug tracker link of errors without fix instructions,
+    /// see [`BugTracker`](crate::BugTracker)
+    pub report_bug: &'static str,
+    /// Asks whether to run a fix action, followed by its description
+    pub run_fix: &'static str,
+    /// Put in front of the description of a fix action that worked
+    pub fix_applied: &'static str,
+    /// Put in front of the description of a fix action that failed
+    pub fix_failed: &'static str,
+    /// The choices of the interactive menu, see
+    /// [`HookConfig::interactive`](crate::HookConfig::interactive)
+    ///
+    /// The letter in brackets shows the key of the choice, which stays the same
+    /// in every language.
+    pub retry: &'static str,
+    pub copy_report: &'static str,
+    pub save_report: &'static str,
+    pub open_tracker: &'static str,
+    pub quit: &'static str,
+    /// Shown after copying the report to the clipboard
+    pub report_copied: &'static str,
+    /// Shown when there is no way to copy to the clipboard
+    pub report_not_copied: &'static str,
+}
+impl Phrases {
+    /// The phrases used when no others are registered for the locale
+    pub const ENGLISH: Phrases = Phrases {
+        header: "The Program Crashed",
+        error_header: "The Program Ran Into an Error",
+        warning_header: "The Program Needs Your Attention",
+        error: "Error",
+        fixable: "It seems like an error that can be fixed by you!\nPlease follow the following instructions to try and fix the Error",
+        unfixable: "It seems like an error that can't be fixed by you!\nPlease submit a Bug report to Developer",
+        technical_details: "Technical details",
+        report_saved: "A report was saved to",
+        report_bug: "Report the bug at",
+        run_fix: "Run the automatic fix",
+        fix_applied: "Fix applied",
+        fix_failed: "Fix failed",
+        retry: "[r]etry",
+        copy_report: "[c]opy the report",
+        save_report: "[s]ave a crash report",
+        open_tracker: "[o]pen the bug tracker",
+        quit: "[q]uit",
+        report_copied: "The report was copied to the clipboard",
+        report_not_copied: "The report could not be copied to the clipboard",
+    };
+
+    /// The first line of the output for errors of `severity`
+    pub fn header_for(&self, severity: Severity) -> &'static str {
+        match severity {
+            Severity::Fatal => self.header,
+            Severity::Error => self.error_header,
+            Severity::Warning => self.warning_header,
+        }
+    }
+}
+
+/// Uses `locale` instead of the one from the environment, `None` goes back
+/// to the environment
+///
+/// Locales look like `de`, `de_DE` or `de-DE`, case doesn't matter.
+pub fn set_locale(locale: Option<&str>) {
+    *LOCALE.write().unwrap_or_else(|e| e.into_inner()) = locale.map(String::from);
+}
+
+/// The locale used for texts missing in the chosen locale, defaults to `en`
+pub fn set_default_locale(locale: &str) {
+    *DEFAULT_LOCALE.write().unwrap_or_else(|e| e.into_inner()) = Some(locale.to_string());
+}
+
+/// Registers the phrases for `locale`, replacing the ones registered before
+///
+/// ```
+/// use user_panic::Phrases;
+///
+/// user_panic::add_phrases("de", Phrases {
+///     header: "Das Programm ist abgestürzt",
+///     error: "Fehler",
+///     ..Phrases::ENGLISH
+/// });
+/// ```
+pub fn add_phrases(locale: &str, phrases: Phrases) {
+    let mut all = PHRASES.write().unwrap_or_else(|e| e.into_inner());
+    all.retain(|(l, _)| normalize(l) != normalize(locale));
+    all.push((locale.to_string(), phrases));
+}
+
+/// The locale chosen for the panic output, `None` if neither [`set_locale`]
+/// nor the environment give one
+pub fn locale() -> Option<String> {
+    if let Some(locale) = &*LOCALE.read().unwrap_or_else(|e| e.into_inner()) {
+        return Some(locale.clone());
+    }
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|value| !value.is_empty())
+        // The C locale means no translation
+        .filter(|value| value != "C" && value != "POSIX")
+}
+
+fn default_locale() -> String {
+    DEFAULT_LOCALE
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+        .unwrap_or_else(|| "en".to_string())
+}
+
+// `de_DE.UTF-8@euro` becomes `de_de`
+fn normalize(locale: &str) -> String {
+    let end = locale.find(['.', '@']).unwrap_or(locale.len());
+    locale[..end].replace('-', "_").to_lowercase()
+}
+
+// The names to look for, from the most to the least specific:
+// the locale, its language and then the same for the default locale
+pub(crate) fn candidates(locale: Option<&str>) -> Vec<String> {
+    let mut names = Vec::new();
+    for locale in locale
+        .map(String::from)
+        .into_iter()
+        .chain([default_locale()])
+    {
+        let locale = normalize(&locale);
+        let language = locale.split_once('_').map(|(l, _)| l.to_string());
+        names.push(locale);
+        names.extend(language);
+    }
+    names
+}
+
+// The first item whose locale is one of the candidates
+pub(crate) fn find<'a, T>(
+    candidates: &[String],
+    items: impl Iterator<Item = (&'a str, T)> + Clone,
+) -> Option<T> {
+    candidates.iter().find_map(|c| {
+        items
+            .clone()
+            .find(|(locale, _)| normalize(locale) == *c)
+            .map(|(_, item)| item)
+    })
+}
+
+pub(crate) fn phrases(candidates: &[String]) -> Phrases {
+    let all = PHRASES.read().unwrap_or_else(|e| e.into_inner());
+    let registered = all.iter().map(|(l, p)| (l.as_str(), *p));
+    find(candidates, registered.chain([("en", Phrases::ENGLISH)])).unwrap_or(Phrases::ENGLISH)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn candidates_from_locale() {
+        assert_eq!(normalize("de_DE.UTF-8@euro"), "de_de");
+        assert_eq!(normalize("pt-BR"), "pt_br");
+        assert_eq!(candidates(Some("de_DE.UTF-8")), vec!["de_de", "de", "en"]);
+        assert_eq!(candidates(Some("fr")), vec!["fr", "en"]);
+        assert_eq!(candidates(None), vec!["en"]);
+    }
+
+    #[test]
+    fn find_most_specific() {
+        let items = [("de", 1), ("de-AT", 2), ("en", 3)];
+        let find = |locale| find(&candidates(Some(locale)), items.iter().copied());
+        assert_eq!(find("de_AT.UTF-8"), Some(2));
+        assert_eq!(find("de_CH"), Some(1));
+        assert_eq!(find("ja_JP"), Some(3));
+        assert_eq!(phrases(&candidates(Some("ja"))), Phrases::ENGLISH);
+    }
+}
@@ -0,0 +1,156 @@
+//! The menu shown after a user panic when the hook is interactive.
+
+use crate::Phrases;
+use std::io::{BufRead, Write};
+use std::process::{Command, Stdio};
+
+/// One entry of the menu, picked by the first letter of the answer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Choice {
+    Retry,
+    Copy,
+    Save,
+    Open,
+    Quit,
+}
+impl Choice {
+    fn key(self) -> char {
+        match self {
+            Choice::Retry => 'r',
+            Choice::Copy => 'c',
+            Choice::Save => 's',
+            Choice::Open => 'o',
+            Choice::Quit => 'q',
+        }
+    }
+    fn label(self, phrases: &Phrases) -> &'static str {
+        match self {
+            Choice::Retry => phrases.retry,
+            Choice::Copy => phrases.copy_report,
+            Choice::Save => phrases.save_report,
+            Choice::Open => phrases.open_tracker,
+            Choice::Quit => phrases.quit,
+        }
+    }
+}
+
+// Asks until retry or quit is chosen, the other choices are handed to `act`
+// and the menu is shown again with the line it returns. `None` when the
+// input ends.
+pub(crate) fn ask(
+    choices: &[Choice],
+    phrases: &Phrases,
+    input: &mut dyn BufRead,
+    output: &mut dyn Write,
+    act: &mut dyn FnMut(Choice) -> Option<String>,
+) -> Option<Choice> {
+    let labels: Vec<&str> = choices.iter().map(|c| c.label(phrases)).collect();
+    loop {
+        write!(output, "\n{} ", labels.join("  ")).ok()?;
+        output.flush().ok()?;
+        let mut answer = String::new();
+        if input.read_line(&mut answer).ok()? == 0 {
+            return None;
+        }
+        let key = answer.trim().chars().next().map(|c| c.to_ascii_lowercase());
+        let Some(&choice) = choices.iter().find(|c| Some(c.key()) == key) else {
+            continue;
+        };
+        match choice {
+            Choice::Retry | Choice::Quit => return Some(choice),
+            _ => {
+                if let Some(line) = act(choice) {
+                    writeln!(output, "{}", line).ok()?;
+                }
+            }
+        }
+    }
+}
+
+// Puts `text` on the clipboard with the tool of the platform
+pub(crate) fn copy(text: &str) -> bool {
+    let tools: &[(&str, &[&str])] = if cfg!(target_os = "macos") {
+        &[("pbcopy", &[])]
+    } else if cfg!(windows) {
+        &[("clip", &[])]
+    } else {
+        &[
+            ("wl-copy", &[]),
+            ("xclip", &["-selection", "clipboard"]),
+            ("xsel", &["--clipboard", "--input"]),
+        ]
+    };
+    tools.iter().any(|(tool, args)| {
+        let child = Command::new(tool)
+            .args(*args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        let Ok(mut child) = child else {
+            return false;
+        };
+        let written = child
+            .stdin
+            .take()
+            .is_some_and(|mut stdin| stdin.write_all(text.as_bytes()).is_ok());
+        child.wait().is_ok_and(|status| status.success()) && written
+    })
+}
+
+// Opens `url` in the browser
+pub(crate) fn open(url: &str) -> bool {
+    let mut command = if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else if cfg!(windows) {
+        // `cmd /C start` would cut the url at the first `&`
+        let mut command = Command::new("rundll32");
+        command.arg("url.dll,FileProtocolHandler");
+        command
+    } else {
+        Command::new("xdg-open")
+    };
+    command
+        .arg(url)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn menu_choices() {
+        let choices = [Choice::Copy, Choice::Save, Choice::Quit];
+        let mut acted = Vec::new();
+        let mut output = Vec::new();
+        let choice = ask(
+            &choices,
+            &Phrases::ENGLISH,
+            &mut "r\nS\n\nc\nquit\n".as_bytes(),
+            &mut output,
+            &mut |c| {
+                acted.push(c);
+                (c == Choice::Save).then(|| "Saved".to_string())
+            },
+        );
+        assert_eq!(choice, Some(Choice::Quit));
+        assert_eq!(acted, [Choice::Save, Choice::Copy]);
+        let menu = "\n[c]opy the report  [s]ave a crash report  [q]uit ";
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            format!("{0}{0}Saved\n{0}{0}{0}", menu)
+        );
+        let choice = ask(
+            &choices,
+            &Phrases::ENGLISH,
+            &mut "".as_bytes(),
+            &mut Vec::new(),
+            &mut |_| None,
+        );
+        assert_eq!(choice, None);
+    }
+}
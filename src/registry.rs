@@ -0,0 +1,368 @@
+//! Panics loaded at runtime instead of generated by a build script.
+//!
+//! The yaml file uses the same layout as the one given to
+//! [`panic_setup!`](crate::panic_setup), so the same file can be shipped next
+//! to the binary or generated on the fly.
+//!
+//! [`UserPanic`] only holds `'static` data so the strings of every registered
+//! panic are leaked. Registries are meant to be built once when the program
+//! starts and kept until it exits.
+
+use crate::schema::{self, Instruction, SchemaError};
+use crate::{FixStep, Translation, UserPanic};
+use std::collections::HashSet;
+use std::fmt;
+use std::io;
+use std::panic::panic_any;
+use std::path::Path;
+use std::sync::RwLock;
+
+// The registry used by `raise`
+static INSTALLED: RwLock<Option<PanicRegistry>> = RwLock::new(None);
+
+/// A set of [`UserPanic`]s that can be looked up by name
+#[derive(Debug, Clone, Default)]
+pub struct PanicRegistry {
+    panics: Vec<UserPanic>,
+}
+impl PanicRegistry {
+    /// An empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Starts building a registry in code
+    pub fn builder() -> PanicRegistryBuilder {
+        PanicRegistryBuilder::default()
+    }
+    /// Reads the panics from a yaml string
+    pub fn from_yaml_str(yaml: &str) -> Result<Self, Vec<SchemaError>> {
+        let mut builder = Self::builder();
+        for entry in schema::parse(yaml)? {
+            let mut panic = leak_panic(
+                &entry.key,
+                &entry.message,
+                entry.fix_instructions.as_deref(),
+                entry.code,
+            );
+            panic.translations = entry
+                .translations
+                .iter()
+                .map(|t| Translation {
+                    locale: leak_str(&t.locale),
+                    error_msg: leak_str(&t.message),
+                    fix_instructions: t.fix_instructions.as_deref().map(leak_steps),
+                })
+                .collect::<Vec<_>>()
+                .leak();
+            panic.exit_code = entry.exit_code;
+            panic.fix_actions = entry
+                .fix_actions
+                .iter()
+                .map(|a| leak_str(a))
+                .collect::<Vec<_>>()
+                .leak();
+            panic.severity = entry.severity;
+            panic.category = entry.category.as_deref().map(leak_str);
+            builder.add(panic);
+        }
+        Ok(builder.build())
+    }
+    /// Reads the panics from a yaml file
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self, LoadError> {
+        let yaml = std::fs::read_to_string(path).map_err(LoadError::Io)?;
+        Self::from_yaml_str(&yaml).map_err(LoadError::Schema)
+    }
+    /// The panic registered under `key`
+    pub fn get(&self, key: &str) -> Option<&UserPanic> {
+        self.panics.iter().find(|p| p.key == key)
+    }
+    /// The panic with the numeric `code`
+    pub fn get_code(&self, code: u32) -> Option<&UserPanic> {
+        self.panics.iter().find(|p| p.code == code)
+    }
+    /// All the panics in the order they were added
+    pub fn iter(&self) -> impl Iterator<Item = &UserPanic> {
+        self.panics.iter()
+    }
+    pub fn len(&self) -> usize {
+        self.panics.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.panics.is_empty()
+    }
+    /// Panics with the panic registered under `key`
+    ///
+    /// Unknown keys cause a regular panic naming the key
+    #[track_caller]
+    pub fn raise(&self, key: &str) -> ! {
+        match self.get(key) {
+            Some(panic) => panic_any(panic.clone()),
+            None => panic!("unknown user panic `{}`", key),
+        }
+    }
+    /// Makes this registry the one used by [`raise`], replacing any previous one
+    pub fn install(self) {
+        *INSTALLED.write().unwrap_or_else(|e| e.into_inner()) = Some(self);
+    }
+}
+
+/// Panics with the panic registered under `key` in the installed [`PanicRegistry`]
+///
+/// ```no_run
+/// user_panic::PanicRegistry::from_path("errors.yaml").unwrap().install();
+/// user_panic::raise("API");
+/// ```
+#[track_caller]
+pub fn raise(key: &str) -> ! {
+    // The lock must not be held while unwinding
+    let panic = match &*INSTALLED.read().unwrap_or_else(|e| e.into_inner()) {
+        Some(registry) => registry.get(key).cloned(),
+        None => panic!("no panic registry installed, can't raise `{}`", key),
+    };
+    match panic {
+        Some(panic) => panic_any(panic),
+        None => panic!("unknown user panic `{}`", key),
+    }
+}
+
+/// Builds a [`PanicRegistry`] in code
+///
+/// ```
+/// use user_panic::{Instruction, PanicRegistry};
+///
+/// let registry = PanicRegistry::builder()
+///     .fixable(
+///         "API",
+///         "There was an error during the API request",
+///         vec![
+///             Instruction::new("Try to check your Internet Connection."),
+///             Instruction::new("Check if your API request quota has been exhausted.")
+///                 .child("Instructions on how")
+///                 .child("to check"),
+///         ],
+///     )
+///     .unfixable("DB", "The database is corrupted")
+///     .build();
+/// assert_eq!(registry.get("DB").unwrap().code, 2);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct PanicRegistryBuilder {
+    panics: Vec<UserPanic>,
+}
+impl PanicRegistryBuilder {
+    /// Adds an error that can't be fixed by the user
+    pub fn unfixable(mut self, key: &str, message: &str) -> Self {
+        self.add(leak_panic(key, message, None, 0));
+        self
+    }
+    /// Adds an error with instructions to fix it
+    pub fn fixable(mut self, key: &str, message: &str, instructions: Vec<Instruction>) -> Self {
+        self.add(leak_panic(key, message, Some(&instructions), 0));
+        self
+    }
+    /// Adds an existing panic, like one of the consts generated by
+    /// [`panic_setup!`](crate::panic_setup)
+    pub fn panic(mut self, panic: UserPanic) -> Self {
+        self.add(panic);
+        self
+    }
+    /// Panics without a code are numbered in the order they were added
+    /// starting at 1, skipping the codes other panics already have
+    pub fn build(mut self) -> PanicRegistry {
+        let taken: HashSet<u32> = self.panics.iter().map(|p| p.code).collect();
+        let mut next = 0;
+        for panic in self.panics.iter_mut().filter(|p| p.code == 0) {
+            next = (next + 1..)
+                .find(|c| !taken.contains(c))
+                .unwrap_or_default();
+            panic.code = next;
+        }
+        PanicRegistry {
+            panics: self.panics,
+        }
+    }
+    // A panic with the same key replaces the previous one
+    fn add(&mut self, panic: UserPanic) {
+        match self.panics.iter_mut().find(|p| p.key == panic.key) {
+            Some(old) => *old = panic,
+            None => self.panics.push(panic),
+        }
+    }
+}
+
+/// Error returned by [`PanicRegistry::from_path`]
+#[derive(Debug)]
+pub enum LoadError {
+    /// The file couldn't be read
+    Io(io::Error),
+    /// The file has mistakes
+    Schema(Vec<SchemaError>),
+}
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Io(e) => write!(f, "failed to read yaml file: {}", e),
+            LoadError::Schema(errors) => {
+                write!(f, "yaml file has {} error(s)", errors.len())?;
+                for e in errors {
+                    write!(f, "\n{}", e)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+impl std::error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LoadError::Io(e) => Some(e),
+            LoadError::Schema(_) => None,
+        }
+    }
+}
+
+fn leak_str(s: &str) -> &'static str {
+    Box::leak(s.to_string().into_boxed_str())
+}
+
+fn leak_steps(instructions: &[Instruction]) -> &'static [FixStep] {
+    instructions
+        .iter()
+        .map(|i| FixStep {
+            text: leak_str(&i.text),
+            children: leak_steps(&i.children),
+        })
+        .collect::<Vec<_>>()
+        .leak()
+}
+
+fn leak_panic(
+    key: &str,
+    message: &str,
+    instructions: Option<&[Instruction]>,
+    code: u32,
+) -> UserPanic {
+    UserPanic {
+        key: leak_str(key),
+        code,
+        ..UserPanic::new(leak_str(message), instructions.map(leak_steps))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Severity;
+
+    const YAML: &str = "
+API:
+    message: There was an error during the API request
+    fix instructions:
+        - Try to check your Internet Connection.
+        - Check if your API request quota has been exhausted.
+        - - Instructions on how
+          - to check
+DB:
+    message: The database is corrupted
+    code: 10
+    severity: warning
+    category: storage
+";
+
+    #[test]
+    fn from_yaml() {
+        let registry = PanicRegistry::from_yaml_str(YAML).unwrap();
+        assert_eq!(registry.len(), 2);
+        let api = registry.get("API").unwrap();
+        assert_eq!(api.code, 1);
+        let steps = api.fix_instructions.unwrap();
+        assert_eq!(steps[1].children[1].text, "to check");
+        let db = registry.get_code(10).unwrap();
+        assert_eq!(
+            (db.key, db.severity, db.category),
+            ("DB", Severity::Warning, Some("storage"))
+        );
+        assert_eq!(api.severity, Severity::Fatal);
+        assert!(registry.get("Nope").is_none());
+    }
+
+    #[test]
+    fn from_yaml_translated() {
+        let registry = PanicRegistry::from_yaml_str(
+            "DB:\n  message:\n    en: The database is corrupted\n    de: Die Datenbank ist kaputt\n",
+        )
+        .unwrap();
+        let db = registry.get("DB").unwrap();
+        assert_eq!(db.error_msg, "The database is corrupted");
+        assert!(db
+            .display_in("de_DE")
+            .contains("Error: Die Datenbank ist kaputt\n"));
+    }
+
+    #[test]
+    fn from_yaml_errors() {
+        let e = PanicRegistry::from_yaml_str("API:\n  mesage: typo\n").unwrap_err();
+        assert_eq!(e.len(), 2);
+        let e = PanicRegistry::from_path("does/not/exist.yaml").unwrap_err();
+        assert!(matches!(e, LoadError::Io(_)));
+    }
+
+    #[test]
+    fn builder() {
+        let from_const = UserPanic {
+            key: "CONST",
+            ..UserPanic::new("From a const", None)
+        };
+        let registry = PanicRegistry::builder()
+            .unfixable("DB", "old message")
+            .fixable(
+                "API",
+                "There was an error during the API request",
+                vec![Instruction::new("one").child(Instruction::new("a").child("i"))],
+            )
+            .panic(from_const.clone())
+            .unfixable("DB", "The database is corrupted")
+            .build();
+        let keys: Vec<_> = registry.iter().map(|p| (p.key, p.code)).collect();
+        assert_eq!(keys, vec![("DB", 1), ("API", 2), ("CONST", 3)]);
+        assert_eq!(
+            registry.get("DB").unwrap().error_msg,
+            "The database is corrupted"
+        );
+        let from_yaml = PanicRegistry::from_yaml_str(
+            "API:\n  message: There was an error during the API request\n  fix instructions:\n    - one\n    - - a\n      - - i\n",
+        )
+        .unwrap();
+        assert_eq!(
+            registry.get("API").unwrap().to_string(),
+            from_yaml.get("API").unwrap().to_string()
+        );
+        let registry = PanicRegistry::builder()
+            .unfixable("DB", "The database is corrupted")
+            .panic(UserPanic {
+                code: 1,
+                ..from_const
+            })
+            .build();
+        let keys: Vec<_> = registry.iter().map(|p| (p.key, p.code)).collect();
+        assert_eq!(keys, vec![("DB", 2), ("CONST", 1)]);
+    }
+
+    #[test]
+    fn raise_panics_with_the_entry() {
+        let registry = PanicRegistry::from_yaml_str(YAML).unwrap();
+        let raise_db = std::panic::AssertUnwindSafe(|| registry.raise("DB"));
+        let payload = std::panic::catch_unwind(raise_db).unwrap_err();
+        let panic = payload.downcast_ref::<UserPanic>().unwrap();
+        assert_eq!(panic.error_msg, "The database is corrupted");
+
+        registry.install();
+        let payload = std::panic::catch_unwind(|| raise("API")).unwrap_err();
+        assert_eq!(payload.downcast_ref::<UserPanic>().unwrap().code, 1);
+        let payload = std::panic::catch_unwind(|| raise("Nope")).unwrap_err();
+        assert_eq!(
+            payload.downcast_ref::<String>().unwrap(),
+            "unknown user panic `Nope`"
+        );
+    }
+}
@@ -0,0 +1,81 @@
+//! A single entry point for `main` that turns user panics into clean exits.
+
+use crate::{hook, HookConfig, UserPanic};
+use std::panic::{self, AssertUnwindSafe, Location};
+
+/// What the closure given to [`run`] can return
+pub trait RunOutput {
+    /// The user panic to report, if any
+    fn into_panic(self) -> Option<UserPanic>;
+}
+impl RunOutput for () {
+    fn into_panic(self) -> Option<UserPanic> {
+        None
+    }
+}
+impl<E: Into<UserPanic>> RunOutput for Result<(), E> {
+    fn into_panic(self) -> Option<UserPanic> {
+        self.err().map(Into::into)
+    }
+}
+
+/// Runs `f` and exits the process with [`UserPanic::exit_code`] if it raises
+/// a [`UserPanic`] or returns an error that converts into one
+///
+/// The panic is reported by the installed [`HookConfig`], or one writing to
+/// stderr if none is installed. Other panics keep unwinding as usual. With an
+/// [`interactive`](HookConfig::interactive) hook the user can retry, which
+/// calls `f` again. The process exits right after `f` unwinds otherwise, so
+/// `f` doesn't need to be [`UnwindSafe`](std::panic::UnwindSafe).
+///
+/// ```no_run
+/// use user_panic::{PanicRegistry, UserPanic};
+///
+/// struct Offline(std::io::Error);
+/// impl From<Offline> for UserPanic {
+///     fn from(Offline(e): Offline) -> UserPanic {
+///         let registry = PanicRegistry::from_path("errors.yaml").unwrap();
+///         registry.get("API").unwrap().clone().with_source(e)
+///     }
+/// }
+/// # fn request() -> std::io::Result<()> { Ok(()) }
+///
+/// user_panic::run(|| {
+///     request().map_err(Offline)?;
+///     Ok::<_, Offline>(())
+/// });
+/// ```
+#[track_caller]
+pub fn run<T: RunOutput>(mut f: impl FnMut() -> T) {
+    let location = Location::caller();
+    // The default hook would only print `Box<dyn Any>` for user panics
+    let _hooks = (!hook::is_installed()).then(|| HookConfig::new().install());
+    let _deferred = Deferred(hook::defer_menu(true));
+    loop {
+        let panic = match panic::catch_unwind(AssertUnwindSafe(&mut f)) {
+            Ok(output) => match output.into_panic() {
+                Some(panic) => {
+                    hook::report(&panic, location);
+                    panic
+                }
+                None => return,
+            },
+            // The hook already reported it
+            Err(payload) => match payload.downcast::<UserPanic>() {
+                Ok(panic) => *panic,
+                Err(payload) => panic::resume_unwind(payload),
+            },
+        };
+        if !hook::ask_retry(&panic) {
+            std::process::exit(panic.exit_code());
+        }
+    }
+}
+
+// Restores who shows the menu when `run` returns or unwinds
+struct Deferred(bool);
+impl Drop for Deferred {
+    fn drop(&mut self) {
+        hook::defer_menu(self.0);
+    }
+}
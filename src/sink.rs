@@ -0,0 +1,282 @@
+//! Where the output of the panic hook goes.
+//!
+//! The hook renders the output once per sink, so a terminal gets colors while
+//! a log file next to it stays plain.
+
+use crate::{Severity, Terminal, UserPanic};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// A destination for the output of the panic hook
+///
+/// ```
+/// use user_panic::{HookConfig, PanicSink, StderrSink, UserPanic};
+///
+/// // Counts the user panics
+/// struct Counter(std::sync::atomic::AtomicUsize);
+/// impl PanicSink for Counter {
+///     fn write(&self, _: &str, panic: Option<&UserPanic>) {
+///         if panic.is_some() {
+///             self.0.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+///         }
+///     }
+/// }
+/// HookConfig::new()
+///     .sink(StderrSink)
+///     .sink(Counter(Default::default()))
+///     .install()
+///     .keep();
+/// ```
+pub trait PanicSink: Send + Sync {
+    /// How the output for this sink is styled, plain text by default
+    fn terminal(&self) -> Terminal {
+        Terminal::PLAIN
+    }
+    /// Receives the whole output for a panic, `panic` is `None` for panics
+    /// that are not [`UserPanic`]s
+    ///
+    /// Panics without any output, like the ones left to the previous hook,
+    /// are not written.
+    fn write(&self, output: &str, panic: Option<&UserPanic>);
+}
+
+/// Prints to stderr, styled when it is a terminal
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StderrSink;
+impl PanicSink for StderrSink {
+    fn terminal(&self) -> Terminal {
+        Terminal::stderr()
+    }
+    fn write(&self, output: &str, _: Option<&UserPanic>) {
+        eprint!("{}", output);
+    }
+}
+
+/// Writes to any [`Write`], like a socket or an in memory buffer
+#[derive(Debug)]
+pub struct WriteSink<W>(Mutex<W>);
+impl<W: Write + Send> WriteSink<W> {
+    pub fn new(writer: W) -> Self {
+        WriteSink(Mutex::new(writer))
+    }
+}
+impl<W: Write + Send> PanicSink for WriteSink<W> {
+    fn write(&self, output: &str, _: Option<&UserPanic>) {
+        let mut writer = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        // There is nowhere left to report a failure to
+        let _ = writer.write_all(output.as_bytes());
+        let _ = writer.flush();
+    }
+}
+
+/// Appends to a file, which is created when missing
+#[derive(Debug, Clone)]
+pub struct FileSink(PathBuf);
+impl FileSink {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        FileSink(path.into())
+    }
+}
+impl PanicSink for FileSink {
+    fn write(&self, output: &str, _: Option<&UserPanic>) {
+        let file = OpenOptions::new().create(true).append(true).open(&self.0);
+        if let Ok(mut file) = file {
+            let _ = file.write_all(output.as_bytes());
+        }
+    }
+}
+
+/// Logs the output with the `log` crate, under the `user_panic` target
+///
+/// Warnings are logged at the warn level and everything else as errors.
+/// Records of user panics carry their `severity` and their `category`, if
+/// they have one, as key-values.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LogSink;
+impl PanicSink for LogSink {
+    fn write(&self, output: &str, panic: Option<&UserPanic>) {
+        let output = output.trim_end();
+        match panic {
+            Some(panic) => {
+                let level = match panic.severity {
+                    Severity::Warning => log::Level::Warn,
+                    Severity::Fatal | Severity::Error => log::Level::Error,
+                };
+                let severity = panic.severity.name();
+                match panic.category {
+                    Some(category) => log::log!(
+                        target: "user_panic",
+                        level,
+                        severity,
+                        category;
+                        "{}",
+                        output
+                    ),
+                    None => log::log!(target: "user_panic", level, severity; "{}", output),
+                }
+            }
+            None => log::error!(target: "user_panic", "{}", output),
+        }
+    }
+}
+
+/// Sends the output to the local syslog daemon, one message per line
+///
+/// Fatal user panics are sent as critical, warnings as warnings and
+/// everything else as errors.
+#[cfg(unix)]
+#[derive(Debug, Clone)]
+pub struct SyslogSink(PathBuf);
+#[cfg(unix)]
+impl SyslogSink {
+    /// Uses the `/dev/log` socket
+    pub fn new() -> Self {
+        Self::at("/dev/log")
+    }
+    /// Uses the unix datagram socket at `path`
+    pub fn at(path: impl Into<PathBuf>) -> Self {
+        SyslogSink(path.into())
+    }
+}
+#[cfg(unix)]
+impl Default for SyslogSink {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+#[cfg(unix)]
+impl PanicSink for SyslogSink {
+    fn write(&self, output: &str, panic: Option<&UserPanic>) {
+        let Ok(socket) = std::os::unix::net::UnixDatagram::unbound() else {
+            return;
+        };
+        let program = std::env::current_exe()
+            .ok()
+            .and_then(|p| p.file_name().map(|n| n.to_string_lossy().into_owned()))
+            .unwrap_or_else(|| "user_panic".into());
+        for line in output.lines().filter(|l| !l.trim().is_empty()) {
+            let message = format!(
+                "<{}>{}[{}]: {}",
+                priority(panic.map(|p| p.severity)),
+                program,
+                std::process::id(),
+                line
+            );
+            let _ = socket.send_to(message.as_bytes(), &self.0);
+        }
+    }
+}
+
+// Facility user (1) with the syslog severity crit (2), err (3) or warning (4),
+// other panics are errors
+#[cfg(unix)]
+fn priority(severity: Option<Severity>) -> u8 {
+    let level = match severity {
+        Some(Severity::Fatal) => 2,
+        Some(Severity::Error) | None => 3,
+        Some(Severity::Warning) => 4,
+    };
+    8 + level
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_sink_appends() {
+        let path = std::env::temp_dir().join(format!("user-panic-{}.log", std::process::id()));
+        let sink = FileSink::new(&path);
+        sink.write("one\n", None);
+        sink.write("two\n", None);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "one\ntwo\n");
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn syslog_sink_sends_lines() {
+        use std::os::unix::net::UnixDatagram;
+        let path = std::env::temp_dir().join(format!("user-panic-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let daemon = UnixDatagram::bind(&path).unwrap();
+        SyslogSink::at(&path).write("Error: broken\n\nMail xyz\n", None);
+        let mut buf = [0; 256];
+        let n = daemon.recv(&mut buf).unwrap();
+        let first = String::from_utf8_lossy(&buf[..n]).into_owned();
+        assert!(first.starts_with("<11>"), "{}", first);
+        assert!(
+            first.ends_with(&format!("[{}]: Error: broken", std::process::id())),
+            "{}",
+            first
+        );
+        let n = daemon.recv(&mut buf).unwrap();
+        assert!(buf[..n].ends_with(b"]: Mail xyz"));
+        let warning = PANIC.with_severity(Severity::Warning);
+        SyslogSink::at(&path).write("Disk almost full\n", Some(&warning));
+        let n = daemon.recv(&mut buf).unwrap();
+        assert!(buf[..n].starts_with(b"<12>"));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn log_sink_levels_and_fields() {
+        use log::kv::{Key, VisitSource};
+
+        struct Fields(Vec<String>);
+        impl<'kvs> VisitSource<'kvs> for Fields {
+            fn visit_pair(
+                &mut self,
+                key: Key<'kvs>,
+                value: log::kv::Value<'kvs>,
+            ) -> Result<(), log::kv::Error> {
+                self.0.push(format!("{}={}", key, value));
+                Ok(())
+            }
+        }
+        static RECORDS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+        struct Logger;
+        impl log::Log for Logger {
+            fn enabled(&self, _: &log::Metadata) -> bool {
+                true
+            }
+            fn log(&self, record: &log::Record) {
+                let mut fields = Fields(Vec::new());
+                record.key_values().visit(&mut fields).unwrap();
+                RECORDS.lock().unwrap().push(format!(
+                    "{} {} {} {}",
+                    record.level(),
+                    record.target(),
+                    fields.0.join(" "),
+                    record.args()
+                ));
+            }
+            fn flush(&self) {}
+        }
+        log::set_logger(&Logger).unwrap();
+        log::set_max_level(log::LevelFilter::Trace);
+        let warning = PANIC.with_severity(Severity::Warning).with_category("disk");
+        LogSink.write("Disk almost full\n", Some(&warning));
+        LogSink.write("Broken\n", Some(&PANIC));
+        LogSink.write("Broken\n", None);
+        let records: Vec<_> = RECORDS
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|r| r.contains(" user_panic "))
+            .cloned()
+            .collect();
+        assert_eq!(
+            records,
+            [
+                "WARN user_panic severity=warning category=disk Disk almost full",
+                "ERROR user_panic severity=fatal Broken",
+                "ERROR user_panic  Broken",
+            ]
+        );
+    }
+
+    const PANIC: UserPanic = UserPanic::new("Broken", None);
+}
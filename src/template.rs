@@ -0,0 +1,170 @@
+//! Layout of the report printed for a [`UserPanic`].
+//!
+//! A report is made of sections, each rendered by a method of
+//! [`ReportTemplate`]. Every method has a default giving the classic output,
+//! so a template only overrides the sections it wants to change.
+
+use crate::{Phrases, Severity, Terminal, UserPanic};
+
+/// What the sections of a report are rendered from
+#[derive(Debug, Clone, Copy)]
+pub struct ReportContext<'a> {
+    /// The panic being reported
+    pub panic: &'a UserPanic,
+    /// The fixed texts in the chosen locale
+    pub phrases: &'a Phrases,
+    /// The message in the chosen locale with the placeholders filled in
+    pub message: &'a str,
+    /// Whether the panic has fix instructions
+    pub fixable: bool,
+    /// Where the report is shown
+    pub terminal: Terminal,
+}
+
+/// A single fix instruction about to be rendered
+#[derive(Debug, Clone, Copy)]
+pub struct StepContext<'a> {
+    /// The number given by [`ReportTemplate::step_label`], like `2.a`
+    pub label: &'a str,
+    /// `1` for the top level instructions, `2` for their children and so on
+    pub depth: usize,
+    /// The instruction with the placeholders filled in
+    pub text: &'a str,
+}
+
+/// The sections of a report, in the order they are printed
+///
+/// ```
+/// use user_panic::{ReportContext, ReportTemplate, StepContext};
+///
+/// struct Plain;
+/// impl ReportTemplate for Plain {
+///     fn header(&self, _: &ReportContext) -> String {
+///         String::new()
+///     }
+///     fn step(&self, _: &ReportContext, step: &StepContext) -> String {
+///         format!("{}- {}\n", "  ".repeat(step.depth), step.text)
+///     }
+/// }
+/// user_panic::HookConfig::new().template(Plain).install().keep();
+/// ```
+pub trait ReportTemplate: Send + Sync {
+    /// First lines of the report
+    fn header(&self, report: &ReportContext) -> String {
+        let term = report.terminal;
+        let severity = report.panic.severity;
+        // Warnings are yellow, errors red
+        let style = if severity == Severity::Warning {
+            "1;33"
+        } else {
+            "1;31"
+        };
+        format!(
+            "{}\n\n",
+            term.paint(style, report.phrases.header_for(severity))
+        )
+    }
+    /// The error message
+    fn error(&self, report: &ReportContext) -> String {
+        let term = report.terminal;
+        let label = format!("{}:", report.phrases.error);
+        let start = label.chars().count() + 1;
+        format!(
+            "{} {}\n",
+            term.paint("1;31", &label),
+            term.paint("1", &term.wrap(report.message, start, start))
+        )
+    }
+    /// Tells whether the user can fix the error
+    fn fix_intro(&self, report: &ReportContext) -> String {
+        let intro = match report.fixable {
+            true => report.phrases.fixable,
+            false => report.phrases.unfixable,
+        };
+        format!("{}\n", report.terminal.wrap(intro, 0, 0))
+    }
+    /// A single fix instruction, its children are rendered right after it
+    fn step(&self, report: &ReportContext, step: &StepContext) -> String {
+        let term = report.terminal;
+        // Top level instructions are separated by an empty line
+        let gap = if step.depth == 1 { "\n" } else { "" };
+        // Tabs have no known width so wrapped output is indented with spaces
+        let indent = match term.width {
+            Some(_) => "    ".repeat(step.depth),
+            None => "\t".repeat(step.depth),
+        };
+        let start = indent.len() + step.label.chars().count() + 2;
+        format!(
+            "{}{}{}: {}\n",
+            gap,
+            indent,
+            term.paint("1;36", step.label),
+            term.wrap(step.text, start, start)
+        )
+    }
+    /// Label of the `number`th instruction at `depth`, `parent` is the label
+    /// of the instruction it belongs to
+    fn step_label(&self, parent: Option<&str>, number: usize, depth: usize) -> String {
+        match parent {
+            Some(parent) => format!("{}.{}", parent, step_number(number, depth)),
+            None => step_number(number, depth),
+        }
+    }
+    /// Printed last by the panic hook, even for panics that are not [`UserPanic`]s
+    fn footer(&self, developer: Option<&str>, terminal: &Terminal) -> String {
+        developer
+            .map(|dev| format!("{}\n", terminal.paint("2", &terminal.wrap(dev, 0, 0))))
+            .unwrap_or_default()
+    }
+}
+
+/// The classic layout with tab indented instructions numbered `1`, `1.a`, `1.a.i`
+///
+/// On a terminal the error line, the numbers and the footer are styled and the
+/// text is wrapped to its width.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultTemplate;
+impl ReportTemplate for DefaultTemplate {}
+
+// Numbering cycles through digits, letters and roman numerals
+pub(crate) fn step_number(n: usize, depth: usize) -> String {
+    match depth % 3 {
+        2 => {
+            let mut s = String::new();
+            let mut n = n;
+            while n > 0 {
+                n -= 1;
+                s.insert(0, (b'a' + (n % 26) as u8) as char);
+                n /= 26;
+            }
+            s
+        }
+        0 => {
+            const ROMAN: [(usize, &str); 13] = [
+                (1000, "m"),
+                (900, "cm"),
+                (500, "d"),
+                (400, "cd"),
+                (100, "c"),
+                (90, "xc"),
+                (50, "l"),
+                (40, "xl"),
+                (10, "x"),
+                (9, "ix"),
+                (5, "v"),
+                (4, "iv"),
+                (1, "i"),
+            ];
+            let mut s = String::new();
+            let mut n = n;
+            for (value, numeral) in ROMAN {
+                while n >= value {
+                    s += numeral;
+                    n -= value;
+                }
+            }
+            s
+        }
+        _ => n.to_string(),
+    }
+}
@@ -0,0 +1,118 @@
+//! Styling and wrapping of the output for the terminal it is shown on.
+
+use std::io::IsTerminal;
+
+/// How the report is shown: with ANSI styles or not, wrapped or not
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Terminal {
+    /// Use ANSI colors and styles
+    pub color: bool,
+    /// Wrap lines to this many columns and indent with spaces instead of tabs
+    pub width: Option<usize>,
+}
+impl Terminal {
+    /// Plain text without wrapping, what [`Display`](std::fmt::Display) uses
+    pub const PLAIN: Terminal = Terminal {
+        color: false,
+        width: None,
+    };
+    /// What stderr can show
+    ///
+    /// Colors and wrapping are only used when stderr is a terminal, colors
+    /// are turned off by setting `NO_COLOR` to anything but an empty string.
+    /// Lines are wrapped to `COLUMNS`, or 80 columns when it is not set.
+    pub fn stderr() -> Terminal {
+        if !std::io::stderr().is_terminal() {
+            return Terminal::PLAIN;
+        }
+        Terminal {
+            color: std::env::var_os("NO_COLOR").map_or(true, |v| v.is_empty()),
+            width: Some(width()),
+        }
+    }
+    /// `text` in the ANSI style `sgr`, like `1` for bold or `1;31` for bold
+    /// red, when colors are on
+    pub fn paint(&self, sgr: &str, text: &str) -> String {
+        match self.color {
+            true => format!("\x1b[{}m{}\x1b[0m", sgr, text),
+            false => text.to_string(),
+        }
+    }
+    /// Wraps `text` at spaces to fit the width
+    ///
+    /// The first line starts at column `start`, the others are indented by
+    /// `indent` spaces. Without a width the text is returned as it is.
+    pub fn wrap(&self, text: &str, start: usize, indent: usize) -> String {
+        let Some(width) = self.width else {
+            return text.to_string();
+        };
+        let mut s = String::new();
+        for (i, line) in text.split('\n').enumerate() {
+            let mut column = start;
+            if i > 0 {
+                s += "\n";
+                s += &" ".repeat(indent);
+                column = indent;
+            }
+            let mut fresh = true;
+            for word in line.split(' ').filter(|w| !w.is_empty()) {
+                let len = word.chars().count();
+                if !fresh && column + 1 + len > width {
+                    s += "\n";
+                    s += &" ".repeat(indent);
+                    column = indent;
+                } else if !fresh {
+                    s += " ";
+                    column += 1;
+                }
+                s += word;
+                column += len;
+                fresh = false;
+            }
+        }
+        s
+    }
+}
+
+// Columns of the terminal as exported by the shell in `COLUMNS`, asking the
+// terminal itself would need platform specific ioctls
+fn width() -> usize {
+    std::env::var("COLUMNS")
+        .ok()
+        .and_then(|c| c.parse().ok())
+        .filter(|c| *c > 0)
+        .unwrap_or(80)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wrap() {
+        let term = Terminal {
+            color: false,
+            width: Some(20),
+        };
+        assert_eq!(
+            term.wrap("Check if your API quota has been exhausted", 7, 7),
+            "Check if your\n       API quota has\n       been\n       exhausted"
+        );
+        assert_eq!(term.wrap("one\ntwo  three", 0, 2), "one\n  two three");
+        assert_eq!(
+            term.wrap("a_word_longer_than_the_width", 5, 0),
+            "a_word_longer_than_the_width"
+        );
+        assert_eq!(Terminal::PLAIN.wrap("a\tb  c", 0, 0), "a\tb  c");
+    }
+
+    #[test]
+    fn paint() {
+        let term = Terminal {
+            color: true,
+            width: None,
+        };
+        assert_eq!(term.paint("1;31", "Error:"), "\x1b[1;31mError:\x1b[0m");
+        assert_eq!(Terminal::PLAIN.paint("1", "Error:"), "Error:");
+    }
+}
@@ -0,0 +1,146 @@
+//! Links to the issue tracker with the bug report already filled in.
+
+use crate::UserPanic;
+
+// Frames of the backtrace put in the report, urls that are too long get
+// rejected by browsers and trackers
+const BACKTRACE_LINES: usize = 20;
+
+/// The issue tracker users report bugs to
+///
+/// The url is a template, these placeholders are replaced by the
+/// percent-encoded values of the panic:
+/// - `{title}`: the key and the message
+/// - `{body}`: the message, key, code, version, platform and the start of the backtrace
+/// - `{key}`, `{message}`, `{version}` and `{os}`
+///
+/// ```
+/// use user_panic::{BugTracker, HookConfig};
+///
+/// HookConfig::new()
+///     .bug_tracker(
+///         BugTracker::new("https://github.com/me/app/issues/new?title={title}&body={body}")
+///             .version(env!("CARGO_PKG_VERSION")),
+///     )
+///     .install()
+///     .keep();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BugTracker {
+    template: String,
+    version: Option<&'static str>,
+}
+impl BugTracker {
+    pub fn new(template: impl Into<String>) -> Self {
+        BugTracker {
+            template: template.into(),
+            version: None,
+        }
+    }
+    /// The version of the program written in the reports
+    pub fn version(mut self, version: &'static str) -> Self {
+        self.version = Some(version);
+        self
+    }
+    // The template filled in for `panic`
+    pub(crate) fn url(&self, panic: &UserPanic, backtrace: Option<&str>) -> String {
+        let message = panic.fill(panic.error_msg);
+        let title = match panic.key {
+            "" => message.to_string(),
+            key => format!("{}: {}", key, message),
+        };
+        let version = self.version.unwrap_or("unknown");
+        let os = format!("{} {}", std::env::consts::OS, std::env::consts::ARCH);
+        let mut body = format!("{}\n\n", message);
+        if !panic.key.is_empty() {
+            body += &format!("Key: {}\n", panic.key);
+        }
+        if panic.code != 0 {
+            body += &format!("Code: {}\n", panic.code);
+        }
+        body += &format!("Version: {}\nPlatform: {}\n", version, os);
+        if let Some(backtrace) = backtrace {
+            let lines: Vec<&str> = backtrace.lines().collect();
+            body += &format!(
+                "\nBacktrace:\n```\n{}\n",
+                lines[..lines.len().min(BACKTRACE_LINES)].join("\n")
+            );
+            if lines.len() > BACKTRACE_LINES {
+                body += "...\n";
+            }
+            body += "```\n";
+        }
+        let mut url = self.template.clone();
+        for (name, value) in [
+            ("title", title.as_str()),
+            ("body", &body),
+            ("key", panic.key),
+            ("message", &message),
+            ("version", version),
+            ("os", &os),
+        ] {
+            url = url.replace(&format!("{{{}}}", name), &encode(value));
+        }
+        url
+    }
+}
+impl From<&str> for BugTracker {
+    fn from(template: &str) -> Self {
+        BugTracker::new(template)
+    }
+}
+impl From<String> for BugTracker {
+    fn from(template: String) -> Self {
+        BugTracker::new(template)
+    }
+}
+
+// Percent-encodes everything but the unreserved characters of RFC 3986
+fn encode(s: &str) -> String {
+    let mut encoded = String::new();
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(b as char)
+            }
+            _ => encoded += &format!("%{:02X}", b),
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filled_url() {
+        assert_eq!(encode("a b&c=ü/~"), "a%20b%26c%3D%C3%BC%2F~");
+        let err = UserPanic {
+            key: "DB",
+            code: 2,
+            ..UserPanic::new("Lost {what}", None)
+        }
+        .with_arg("what", "rows");
+        let tracker = BugTracker::new("https://x.org/new?title={title}&v={version}&body={body}")
+            .version("1.0");
+        let url = tracker.url(&err, None);
+        let body = format!(
+            "Lost rows\n\nKey: DB\nCode: 2\nVersion: 1.0\nPlatform: {} {}\n",
+            std::env::consts::OS,
+            std::env::consts::ARCH
+        );
+        assert_eq!(
+            url,
+            format!(
+                "https://x.org/new?title=DB%3A%20Lost%20rows&v=1.0&body={}",
+                encode(&body)
+            )
+        );
+        let backtrace: String = (0..30).map(|i| format!("{}: f\n", i)).collect();
+        let url = BugTracker::new("{body}").url(&err, Some(&backtrace));
+        assert!(url.contains(&encode("19: f\n...\n```\n")), "{}", url);
+        assert!(!url.contains(&encode("20: f")), "{}", url);
+        assert!(url.contains("Version%3A%20unknown"), "{}", url);
+    }
+}
@@ -0,0 +1,156 @@
+use std::io::Write;
+use std::panic::{catch_unwind, panic_any, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use user_panic::{
+    ColorChoice, FixActionMode, Format, HookConfig, PanicRegistry, PanicSink, UserPanic, WriteSink,
+};
+
+// A writer whose output can still be read after giving it to the hook
+#[derive(Clone, Default)]
+struct Output(Arc<Mutex<Vec<u8>>>);
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+impl Output {
+    fn take(&self) -> String {
+        String::from_utf8(std::mem::take(&mut *self.0.lock().unwrap())).unwrap()
+    }
+}
+
+// Counts how often the hook writes to it
+#[derive(Clone, Default)]
+struct Writes(Arc<AtomicUsize>);
+impl PanicSink for Writes {
+    fn write(&self, _: &str, _: Option<&UserPanic>) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+// The hook is global so everything is checked in a single test
+#[test]
+fn hook_config() {
+    let registry = PanicRegistry::builder()
+        .unfixable("DB", "The database is corrupted")
+        .build();
+    let db = registry.get("DB").unwrap().clone();
+    let output = Output::default();
+    let copy = Output::default();
+
+    let text = HookConfig::new()
+        .developer("Mail xyz")
+        .writer(output.clone())
+        .sink(WriteSink::new(copy.clone()))
+        .color(ColorChoice::Always)
+        .chain(false)
+        .install();
+    catch_unwind(AssertUnwindSafe(|| panic_any(db.clone()))).unwrap_err();
+    let s = output.take();
+    assert_eq!(s, copy.take());
+    assert!(
+        s.starts_with("\x1b[1;31mThe Program Crashed\x1b[0m\n\n"),
+        "{:?}",
+        s
+    );
+    assert!(
+        s.ends_with("Bug report to Developer\n\n\x1b[2mMail xyz\x1b[0m\n"),
+        "{:?}",
+        s
+    );
+    catch_unwind(|| panic!("plain {}", 1)).unwrap_err();
+    let s = output.take();
+    assert!(
+        s.starts_with("thread 'hook_config' panicked at tests/hook.rs:"),
+        "{}",
+        s
+    );
+    assert!(s.ends_with(":\nplain 1\n\x1b[2mMail xyz\x1b[0m\n"), "{}", s);
+
+    let json = HookConfig::new()
+        .developer("Mail xyz")
+        .writer(output.clone())
+        .format(Format::Json)
+        .install();
+    catch_unwind(AssertUnwindSafe(|| {
+        let _context = user_panic::context!("opening {}", "app.db");
+        panic_any(db.clone())
+    }))
+    .unwrap_err();
+    let s = output.take();
+    assert!(
+        s.starts_with("{\"code\":1,\"key\":\"DB\",\"message\":\"The database is corrupted\""),
+        "{}",
+        s
+    );
+    assert_eq!(s.lines().count(), 1, "{}", s);
+    assert!(
+        s.contains("\"thread\":\"hook_config\",\"context\":[\"opening app.db\"]"),
+        "{}",
+        s
+    );
+    // Other panics are objects too and the developer message stays inside them
+    catch_unwind(|| panic!("plain {}", 2)).unwrap_err();
+    let s = output.take();
+    assert!(
+        s.starts_with(
+            "{\"code\":null,\"key\":null,\"message\":\"plain 2\",\"developer\":\"Mail xyz\","
+        ),
+        "{}",
+        s
+    );
+    assert_eq!(s.lines().count(), 1, "{}", s);
+    catch_unwind(|| panic_any(UserPanic::new("", None))).unwrap_err();
+    assert_eq!(output.take(), "");
+
+    // Dropping the guards brings back the previous configuration, then the
+    // original hook
+    drop(json);
+    catch_unwind(AssertUnwindSafe(|| panic_any(db.clone()))).unwrap_err();
+    let s = output.take();
+    assert!(s.starts_with("\x1b[1;31mThe Program Crashed"), "{}", s);
+    // The copy still has the plain panic from before
+    assert!(copy.take().ends_with(&s));
+    drop(text);
+    catch_unwind(AssertUnwindSafe(|| panic_any(db.clone()))).unwrap_err();
+    assert_eq!(output.take(), "");
+    assert_eq!(copy.take(), "");
+
+    // Fix actions run after the report and their outcome goes to the sinks
+    user_panic::add_fix_action(
+        "reset",
+        "Reset the database",
+        || Ok::<_, std::io::Error>(()),
+    );
+    let _fixing = HookConfig::new()
+        .writer(output.clone())
+        .fix_actions(FixActionMode::Always)
+        .bug_tracker("https://x.org/new?title={title}")
+        .install();
+    let fixable = UserPanic {
+        fix_actions: &["reset"],
+        ..db.clone()
+    };
+    catch_unwind(AssertUnwindSafe(|| panic_any(fixable))).unwrap_err();
+    let s = output.take();
+    assert!(
+        s.ends_with(
+            "Developer\n\nReport the bug at https://x.org/new?title=DB%3A%20The%20database%20is%20corrupted\n\
+             Fix applied: Reset the database\n"
+        ),
+        "{}",
+        s
+    );
+
+    // Other panics are only printed by the original hook
+    let writes = Writes::default();
+    let _chained = HookConfig::new().sink(writes.clone()).install();
+    catch_unwind(|| panic!("plain")).unwrap_err();
+    assert_eq!(writes.0.load(Ordering::Relaxed), 0);
+    catch_unwind(AssertUnwindSafe(|| panic_any(db.clone()))).unwrap_err();
+    assert_eq!(writes.0.load(Ordering::Relaxed), 1);
+}
@@ -0,0 +1,57 @@
+use std::process::Command;
+use user_panic::{PanicRegistry, UserPanic};
+
+fn registry() -> PanicRegistry {
+    PanicRegistry::from_yaml_str(
+        "API:\n  message: The API is down\n  exit code: 69\nDB:\n  message: The database is corrupted\n",
+    )
+    .unwrap()
+}
+
+// An error of the program that converts into a user panic
+struct Offline;
+impl From<Offline> for UserPanic {
+    fn from(_: Offline) -> UserPanic {
+        registry().get("API").unwrap().clone()
+    }
+}
+
+// What the copy of this test started by `run_exits_with_the_code` does
+fn child(case: &str) {
+    match case {
+        "err" => user_panic::run(|| Err(Offline)),
+        "panic" => user_panic::run::<()>(|| registry().raise("DB")),
+        "other" => user_panic::run::<()>(|| panic!("plain")),
+        // `run` exits instead of the hook, after offering its menu
+        "exit" => {
+            let _hooks = user_panic::HookConfig::new().exit(true).install();
+            user_panic::run::<()>(|| registry().raise("DB"))
+        }
+        _ => user_panic::run(|| {}),
+    }
+}
+
+// `run` exits the process so every case runs in its own copy of this test
+#[test]
+fn run_exits_with_the_code() {
+    if let Ok(case) = std::env::var("USER_PANIC_RUN") {
+        return child(&case);
+    }
+    for (case, code, output) in [
+        ("err", 69, "Error: The API is down\n"),
+        ("panic", 3, "Error: The database is corrupted\n"),
+        ("other", 101, "panicked at tests/run.rs:"),
+        ("exit", 3, "Error: The database is corrupted\n"),
+        ("ok", 0, ""),
+    ] {
+        let out = Command::new(std::env::current_exe().unwrap())
+            .args(["run_exits_with_the_code", "--exact", "--nocapture"])
+            .env("USER_PANIC_RUN", case)
+            .env("NO_COLOR", "1")
+            .output()
+            .unwrap();
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        assert_eq!(out.status.code(), Some(code), "{}: {}", case, stderr);
+        assert!(stderr.contains(output), "{}: {}", case, stderr);
+    }
+}
@@ -0,0 +1,578 @@
+//! Generation of the rust source for the panic structs.
+
+use crate::schema::{self, Entry, Instruction, SchemaError, SchemaErrorKind};
+use log::debug;
+use std::collections::HashMap;
+
+/// Options for the code generated by `panic_setup!` and `include_panics!`
+#[derive(Debug, Clone, Default)]
+pub struct SetupOptions {
+    /// Turn error names like `api-error` or `apiError` into `API_ERROR`
+    /// instead of rejecting names that are not valid rust identifiers
+    pub normalize_names: bool,
+}
+
+/// The imports needed by the generated items, not every file needs all of them
+pub const PRELUDE: &str =
+    "#[allow(unused_imports)]\nuse user_panic::{FixStep, Severity, Translation, UserPanic};";
+
+/// The contents of the file written by `panic_setup!`
+pub fn read_from_yml(yaml: String, options: &SetupOptions) -> Result<String, Vec<SchemaError>> {
+    Ok(format!("{}\n{}", PRELUDE, generate(&yaml, options)?))
+}
+
+/// The generated items, expecting the [`PRELUDE`] to be in scope
+pub fn generate(yaml: &str, options: &SetupOptions) -> Result<String, Vec<SchemaError>> {
+    let entries = schema::parse(yaml)?;
+    let names = const_names(&entries, options)?;
+    let mut file = String::new();
+    for (entry, name) in entries.iter().zip(&names) {
+        match &entry.params {
+            // Templates get a function taking every param instead of a const,
+            // so a missing or unknown param doesn't compile
+            Some(params) => {
+                debug!("generating constructor {}", name);
+                let args: Vec<String> = params
+                    .iter()
+                    .map(|p| format!("{}: impl ToString", p))
+                    .collect();
+                let values: String = params
+                    .iter()
+                    .map(|p| format!("({:?},{}.to_string()),", p, p))
+                    .collect();
+                file += &format!(
+                    "#[allow(non_snake_case)]\npub fn {}({}) -> UserPanic {{UserPanic {{{}args:vec![{}],}}}}\n",
+                    name,
+                    args.join(", "),
+                    fields(entry),
+                    values
+                );
+            }
+            None => {
+                debug!("generating const {}", name);
+                file += &format!(
+                    "pub const {}:UserPanic = UserPanic {{{}args:Vec::new(),}};",
+                    name,
+                    fields(entry)
+                );
+            }
+        }
+    }
+    file += &panic_code(&entries, &names);
+    Ok(file)
+}
+
+// The `PanicCode` enum with one variant per entry
+fn panic_code(entries: &[Entry], names: &[String]) -> String {
+    let mut variants = String::new();
+    let mut all = String::new();
+    let mut codes = String::new();
+    let mut keys = String::new();
+    let mut into_panics = String::new();
+    let mut from_keys = String::new();
+    for (entry, name) in entries.iter().zip(names) {
+        let variant = format!("PanicCode::{}", pascal_case(name));
+        variants += &format!("    {},\n", pascal_case(name));
+        all += &format!("{}, ", variant);
+        codes += &format!("            {} => {},\n", variant, entry.code);
+        keys += &format!("            {} => {:?},\n", variant, entry.key);
+        into_panics += &match entry.params {
+            // Templates are left unfilled
+            Some(_) => format!(
+                "            {} => UserPanic {{{}args:Vec::new(),}},\n",
+                variant,
+                fields(entry)
+            ),
+            None => format!("            {} => {},\n", variant, name),
+        };
+        from_keys += &format!("            {:?} => Ok({}),\n", entry.key, variant);
+    }
+    format!(
+        "
+/// Every panic defined in the yaml file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PanicCode {{
+{variants}}}
+impl PanicCode {{
+    /// All the panics in the order of the yaml file
+    pub const fn all() -> &'static [PanicCode] {{
+        &[{all}]
+    }}
+    /// Stable numeric code of the panic
+    pub const fn code(self) -> u32 {{
+        match self {{
+{codes}        }}
+    }}
+    /// Name of the panic in the yaml file
+    pub const fn key(self) -> &'static str {{
+        match self {{
+{keys}        }}
+    }}
+    /// Looks up a panic by its numeric code
+    pub fn from_code(code: u32) -> Option<PanicCode> {{
+        PanicCode::all().iter().copied().find(|c| c.code() == code)
+    }}
+    /// The code of a panic payload, `None` if it is not one of these panics
+    pub fn of(panic: &UserPanic) -> Option<PanicCode> {{
+        PanicCode::from_code(panic.code).filter(|c| c.key() == panic.key)
+    }}
+}}
+impl From<PanicCode> for UserPanic {{
+    fn from(code: PanicCode) -> UserPanic {{
+        match code {{
+{into_panics}        }}
+    }}
+}}
+impl std::str::FromStr for PanicCode {{
+    type Err = user_panic::UnknownPanicCode;
+    /// Parses the name of the panic in the yaml file or its numeric code
+    fn from_str(s: &str) -> Result<PanicCode, Self::Err> {{
+        match s {{
+{from_keys}            _ => s
+                .parse()
+                .ok()
+                .and_then(PanicCode::from_code)
+                .ok_or_else(|| user_panic::UnknownPanicCode(s.to_string())),
+        }}
+    }}
+}}
+impl std::fmt::Display for PanicCode {{
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {{
+        f.write_str(self.key())
+    }}
+}}
+"
+    )
+}
+
+// Names of the generated consts, in the same order as the entries
+fn const_names(entries: &[Entry], options: &SetupOptions) -> Result<Vec<String>, Vec<SchemaError>> {
+    let mut names = Vec::new();
+    let mut errors = Vec::new();
+    // Names that only differ by case are rejected too, they would end up as the
+    // same `PanicCode` variant and are confusing to use anyway
+    let mut seen: HashMap<String, &Entry> = HashMap::new();
+    for entry in entries {
+        let name = if options.normalize_names {
+            screaming_snake_case(&entry.key)
+        } else {
+            entry.key.clone()
+        };
+        let variant = pascal_case(&name);
+        // Names made only of underscores have no letters left for the variant
+        if let Err(kind) = check_ident(&name).and(check_ident(&variant)) {
+            errors.push(SchemaError::new(kind, Some(&entry.key), entry.mark));
+            continue;
+        }
+        // Params become the arguments of the generated function
+        let params = entry.params.iter().flatten();
+        if params.clone().any(|p| check_ident(p).is_err()) {
+            errors.push(SchemaError::new(
+                SchemaErrorKind::InvalidParams,
+                Some(&entry.key),
+                entry.mark,
+            ));
+        }
+        match seen.get(&variant) {
+            Some(first) => errors.push(SchemaError::new(
+                SchemaErrorKind::DuplicateKey {
+                    other: first.key.clone(),
+                    line: first.mark.line,
+                    col: first.mark.col,
+                },
+                Some(&entry.key),
+                entry.mark,
+            )),
+            None => {
+                seen.insert(variant, entry);
+            }
+        }
+        names.push(name);
+    }
+    if errors.is_empty() {
+        Ok(names)
+    } else {
+        Err(errors)
+    }
+}
+
+// Strict and reserved keywords of the 2021 edition
+const KEYWORDS: &[&str] = &[
+    "as", "break", "const", "continue", "crate", "else", "enum", "extern", "false", "fn", "for",
+    "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return",
+    "self", "Self", "static", "struct", "super", "trait", "true", "type", "unsafe", "use", "where",
+    "while", "async", "await", "dyn", "abstract", "become", "box", "do", "final", "macro",
+    "override", "priv", "typeof", "unsized", "virtual", "yield", "try", "_",
+];
+
+fn check_ident(name: &str) -> Result<(), SchemaErrorKind> {
+    let mut chars = name.chars();
+    let valid = match chars.next() {
+        Some(c) if c == '_' || c.is_alphabetic() => chars.all(|c| c == '_' || c.is_alphanumeric()),
+        _ => false,
+    };
+    if !valid {
+        Err(SchemaErrorKind::InvalidIdent)
+    } else if KEYWORDS.contains(&name) {
+        Err(SchemaErrorKind::Keyword)
+    } else {
+        Ok(())
+    }
+}
+
+// `apiError`, `api-error` and `API error` all become `API_ERROR`
+fn screaming_snake_case(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    let mut s = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        if !c.is_alphanumeric() {
+            if !s.is_empty() && !s.ends_with('_') {
+                s.push('_');
+            }
+            continue;
+        }
+        let prev = if i > 0 { chars[i - 1] } else { '_' };
+        let next = chars.get(i + 1).copied().unwrap_or('_');
+        // A new word starts at `aB` and at the `B` of `ABc`
+        let boundary = c.is_uppercase()
+            && (prev.is_lowercase()
+                || prev.is_numeric()
+                || (prev.is_uppercase() && next.is_lowercase()));
+        if boundary && !s.is_empty() && !s.ends_with('_') {
+            s.push('_');
+        }
+        s.extend(c.to_uppercase());
+    }
+    let s = s.trim_end_matches('_').to_string();
+    // Identifiers can't start with a digit
+    match s.chars().next() {
+        Some(c) if c.is_numeric() => format!("_{}", s),
+        _ => s,
+    }
+}
+
+// `API_ERROR`, `api_error` and `apiError` all become `ApiError`
+fn pascal_case(name: &str) -> String {
+    let mut s = String::new();
+    for word in screaming_snake_case(name).split('_') {
+        let mut chars = word.chars();
+        if let Some(first) = chars.next() {
+            s.push(first);
+            s.extend(chars.flat_map(char::to_lowercase));
+        }
+    }
+    match s.chars().next() {
+        Some(c) if c.is_numeric() => format!("_{}", s),
+        _ if s == "Self" => String::from("Self_"),
+        _ => s,
+    }
+}
+
+// The struct fields of an entry
+// Every string is written with `{:?}` so quotes, backslashes and newlines
+// in the yaml end up as valid escapes in the rust literal
+fn fields(entry: &Entry) -> String {
+    let s = match &entry.fix_instructions {
+        Some(steps) => format!(
+            "error_msg:{:?},fix_instructions:Some({}),",
+            entry.message,
+            step_list(steps)
+        ),
+        None => format!("error_msg:{:?},fix_instructions: None,", entry.message),
+    };
+    let mut s = s + &format!(
+        "key:{:?},code:{},exit_code:{:?},source:None,",
+        entry.key, entry.code, entry.exit_code
+    );
+    s += "translations:&[";
+    for t in &entry.translations {
+        s += &format!(
+            "Translation{{locale:{:?},error_msg:{:?},fix_instructions:{}}},",
+            t.locale,
+            t.message,
+            match &t.fix_instructions {
+                Some(steps) => format!("Some({})", step_list(steps)),
+                None => "None".to_string(),
+            }
+        );
+    }
+    s += "],fix_actions:&[";
+    for action in &entry.fix_actions {
+        s += &format!("{:?},", action);
+    }
+    s += &format!(
+        "],severity:Severity::{:?},category:{:?},",
+        entry.severity, entry.category
+    );
+    s
+}
+
+// A `&[FixStep]` literal, recursing into the sub instructions
+fn step_list(steps: &[Instruction]) -> String {
+    let mut s = String::from("&[");
+    for step in steps {
+        s += &format!(
+            "FixStep{{text:{:?},children:{}}},",
+            step.text,
+            step_list(&step.children)
+        );
+    }
+    s + "]"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn print_s() {
+        //        env_logger::init();
+        let s = "
+foo:
+    message: this is the main error
+    fix instructions:
+        - first
+        - - in first
+          - in first second
+        - second
+        - - second first
+          - second second
+        - third
+bar:
+    message: This is un fixable error
+";
+        let s = read_from_yml(s.to_string(), &SetupOptions::default()).unwrap();
+        assert!(s.starts_with("#[allow(unused_imports)]\nuse user_panic::{FixStep, Severity, Translation, UserPanic};\npub const foo:UserPanic = UserPanic {error_msg:\"this is the main error\",fix_instructions:Some(&[FixStep{text:\"first\",children:&[FixStep{text:\"in first\",children:&[]},FixStep{text:\"in first second\",children:&[]},]},FixStep{text:\"second\",children:&[FixStep{text:\"second first\",children:&[]},FixStep{text:\"second second\",children:&[]},]},FixStep{text:\"third\",children:&[]},]),key:\"foo\",code:1,exit_code:None,source:None,translations:&[],fix_actions:&[],severity:Severity::Fatal,category:None,args:Vec::new(),};pub const bar:UserPanic = UserPanic {error_msg:\"This is un fixable error\",fix_instructions: None,key:\"bar\",code:2,exit_code:None,source:None,translations:&[],fix_actions:&[],severity:Severity::Fatal,category:None,args:Vec::new(),};\n"), "{}", s);
+    }
+
+    // Reads back a rust string literal, failing on anything rustc would reject
+    fn unescape(lit: &str) -> String {
+        let mut chars = lit
+            .strip_prefix('"')
+            .and_then(|l| l.strip_suffix('"'))
+            .expect("not a string literal")
+            .chars();
+        let mut out = String::new();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => panic!("unescaped quote in {}", lit),
+                '\\' => match chars.next().unwrap() {
+                    'n' => out.push('\n'),
+                    't' => out.push('\t'),
+                    'r' => out.push('\r'),
+                    '0' => out.push('\0'),
+                    '\\' => out.push('\\'),
+                    '"' => out.push('"'),
+                    '\'' => out.push('\''),
+                    'u' => {
+                        let hex: String =
+                            chars.by_ref().skip(1).take_while(|c| *c != '}').collect();
+                        out.push(char::from_u32(u32::from_str_radix(&hex, 16).unwrap()).unwrap());
+                    }
+                    e => panic!("invalid escape \\{} in {}", e, lit),
+                },
+                c => out.push(c),
+            }
+        }
+        out
+    }
+
+    // The line with the consts, escaped newlines keep them all on a single line
+    fn consts(code: &str) -> &str {
+        code.lines().nth(PRELUDE.lines().count()).unwrap()
+    }
+
+    // Splits the generated code of a single entry into its string literals
+    fn literals(code: &str) -> Vec<String> {
+        let mut lits = Vec::new();
+        let mut rest = code;
+        while let Some(start) = rest.find('"') {
+            let mut end = start + 1;
+            let bytes = rest.as_bytes();
+            while bytes[end] != b'"' {
+                end += if bytes[end] == b'\\' { 2 } else { 1 };
+            }
+            lits.push(unescape(&rest[start..=end]));
+            rest = &rest[end + 1..];
+        }
+        lits
+    }
+
+    #[test]
+    fn nasty_strings_are_escaped() {
+        let corpus = [
+            r#"He said "hi""#,
+            r"C:\Users\path\",
+            "{} and {0} and {{braces}}",
+            "tabs\tin\tthe\tmiddle",
+            "unicode: héllo wörld ✓ 日本語 🦀",
+            "combining e\u{301} and zero\u{200b}width",
+            "nul \0 and bell \u{7}",
+            "'single' quotes",
+            "a trailing backslash \\",
+        ];
+        for msg in corpus {
+            // yaml escapes control characters as \xXX instead of \u{XX}
+            let quoted: String = msg
+                .chars()
+                .map(|c| match c {
+                    '"' | '\\' => format!("\\{}", c),
+                    c if c.is_control() => format!("\\x{:02x}", c as u32),
+                    c => c.to_string(),
+                })
+                .collect();
+            let yaml = format!("foo:\n  message: \"{}\"\n", quoted);
+            let code = read_from_yml(yaml, &SetupOptions::default()).unwrap();
+            assert_eq!(literals(consts(&code)), vec![msg, "foo"], "{}", code);
+        }
+    }
+
+    #[test]
+    fn block_scalars_are_escaped() {
+        let yaml = "
+foo:
+    message: |
+        first line with \"quotes\"
+        second line with a \\ backslash
+    fix instructions:
+        - >
+          folded lines
+          end up joined
+        - - |
+            nested literal
+            keeps {braces}
+";
+        let code = read_from_yml(yaml.to_string(), &SetupOptions::default()).unwrap();
+        assert_eq!(
+            literals(consts(&code)),
+            vec![
+                "first line with \"quotes\"\nsecond line with a \\ backslash\n",
+                "folded lines end up joined\n",
+                "nested literal\nkeeps {braces}\n",
+                "foo",
+            ]
+        );
+    }
+
+    #[test]
+    fn errors_are_collected() {
+        let e = read_from_yml(
+            "a:\n  message: 1\nb:\n  fix instructions: []\n".into(),
+            &SetupOptions::default(),
+        )
+        .unwrap_err();
+        assert_eq!(e.len(), 2);
+        assert_eq!(e[0].key.as_deref(), Some("a"));
+        assert_eq!(e[1].key.as_deref(), Some("b"));
+    }
+
+    #[test]
+    fn panic_code_enum() {
+        let yaml = "API:\n  message: x\ndb-down:\n  message: y\n  code: 7\n";
+        let options = SetupOptions {
+            normalize_names: true,
+        };
+        let s = read_from_yml(yaml.to_string(), &options).unwrap();
+        for line in [
+            "pub enum PanicCode {\n    Api,\n    DbDown,\n}",
+            "&[PanicCode::Api, PanicCode::DbDown, ]",
+            "PanicCode::Api => 1,",
+            "PanicCode::DbDown => 7,",
+            "PanicCode::DbDown => \"db-down\",",
+            "PanicCode::DbDown => DB_DOWN,",
+            "\"db-down\" => Ok(PanicCode::DbDown),",
+        ] {
+            assert!(s.contains(line), "{} not in {}", line, s);
+        }
+        // An empty file still has a usable enum
+        let s = read_from_yml(String::new(), &SetupOptions::default()).unwrap();
+        assert!(s.contains("pub enum PanicCode {\n}"));
+        assert!(s.contains("&[]"));
+    }
+
+    #[test]
+    fn templates() {
+        let yaml = "
+ConfigMissing:
+    message: Could not open {path}
+    params: [path, dir]
+    fix instructions:
+        - Create {path} in {dir}
+";
+        let s = read_from_yml(yaml.to_string(), &SetupOptions::default()).unwrap();
+        let line = s.lines().find(|l| l.starts_with("pub fn")).unwrap();
+        assert_eq!(line, "pub fn ConfigMissing(path: impl ToString, dir: impl ToString) -> UserPanic {UserPanic {error_msg:\"Could not open {path}\",fix_instructions:Some(&[FixStep{text:\"Create {path} in {dir}\",children:&[]},]),key:\"ConfigMissing\",code:1,exit_code:None,source:None,translations:&[],fix_actions:&[],severity:Severity::Fatal,category:None,args:vec![(\"path\",path.to_string()),(\"dir\",dir.to_string()),],}}");
+        assert!(s.contains("PanicCode::ConfigMissing => UserPanic {error_msg:"));
+        let e = read_from_yml(
+            "foo:\n  message: \"{type}\"\n  params: [type]\n".to_string(),
+            &SetupOptions::default(),
+        )
+        .unwrap_err();
+        assert_eq!(e[0].kind, SchemaErrorKind::InvalidParams);
+    }
+
+    fn names(yaml: &str, normalize_names: bool) -> Result<Vec<String>, Vec<SchemaError>> {
+        let entries = schema::parse(yaml).unwrap();
+        const_names(&entries, &SetupOptions { normalize_names })
+    }
+
+    #[test]
+    fn names_must_be_identifiers() {
+        let e = names("api-error:\n  message: x\n", false);
+        assert_eq!(e.unwrap_err()[0].kind, SchemaErrorKind::InvalidIdent);
+        let e = names("'404':\n  message: y\n", false).unwrap_err();
+        assert_eq!(e[0].kind, SchemaErrorKind::InvalidIdent);
+        assert_eq!(e[0].key.as_deref(), Some("404"));
+        let e = names("type:\n  message: x\n", false).unwrap_err();
+        assert_eq!(e[0].kind, SchemaErrorKind::Keyword);
+        let e = names("_:\n  message: x\n", false).unwrap_err();
+        assert_eq!(e[0].kind, SchemaErrorKind::Keyword);
+        let e = names("__:\n  message: x\n", false).unwrap_err();
+        assert_eq!(e[0].kind, SchemaErrorKind::InvalidIdent);
+        assert!(names(
+            "API:\n  message: x\n_Db2:\n  message: x\nÉCHEC:\n  message: x\n",
+            false
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn case_collisions() {
+        let e = names("API:\n  message: x\nApi:\n  message: y\n", false).unwrap_err();
+        assert_eq!(
+            e[0].kind,
+            SchemaErrorKind::DuplicateKey {
+                other: "API".into(),
+                line: 1,
+                col: 1
+            }
+        );
+        assert_eq!((e[0].line, e[0].col), (3, 1));
+        let e = names("api-error:\n  message: x\napiError:\n  message: y\n", true).unwrap_err();
+        assert_eq!(
+            e[0].to_string(),
+            "3:1: in `apiError`: collides with `api-error` defined at 1:1"
+        );
+        let e = names("A1:\n  message: x\nA_1:\n  message: y\n", false).unwrap_err();
+        assert_eq!(e[0].key.as_deref(), Some("A_1"));
+    }
+
+    #[test]
+    fn normalized_names() {
+        for (key, name) in [
+            ("api-error", "API_ERROR"),
+            ("apiError", "API_ERROR"),
+            ("HTTPError", "HTTP_ERROR"),
+            ("config missing", "CONFIG_MISSING"),
+            ("404", "_404"),
+            ("type", "TYPE"),
+            ("Db2Down", "DB2_DOWN"),
+            ("ALREADY_FINE", "ALREADY_FINE"),
+        ] {
+            let yaml = format!("'{}':\n  message: x\n", key);
+            assert_eq!(names(&yaml, true).unwrap(), vec![name]);
+        }
+        let e = names("'---':\n  message: x\n", true).unwrap_err();
+        assert_eq!(e[0].kind, SchemaErrorKind::InvalidIdent);
+    }
+}
@@ -0,0 +1,9 @@
+//! The yaml parsing and code generation behind [user-panic](https://docs.rs/user-panic).
+//!
+//! Shared by the build script functions of `user-panic` and the
+//! `include_panics!` macro of `user-panic-macros`, which can't depend on
+//! `user-panic` itself. Use them through `user-panic` instead of depending on
+//! this crate directly, its items can change in any release.
+
+pub mod codegen;
+pub mod schema;
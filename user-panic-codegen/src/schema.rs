@@ -0,0 +1,1024 @@
+//! Parsing of the errors yaml file into panic entries.
+//!
+//! `yaml_rust::YamlLoader` throws away source positions, so the file is loaded
+//! through the low level parser into a tree of `Node`s that remember where
+//! they came from. Every problem found while reading the entries is reported
+//! as a [`SchemaError`] pointing at the offending line and column.
+
+use log::debug;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use yaml_rust::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust::scanner::{Marker, ScanError, TScalarStyle};
+use yaml_rust::Yaml;
+
+/// Position of a node in the yaml source
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mark {
+    /// 1 based line number
+    pub line: usize,
+    /// 1 based column number
+    pub col: usize,
+}
+impl From<Marker> for Mark {
+    fn from(m: Marker) -> Self {
+        // yaml_rust lines start at 1 but columns at 0
+        Mark {
+            line: m.line(),
+            col: m.col() + 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Node {
+    pub value: Value,
+    pub mark: Mark,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum Value {
+    /// Resolved scalar, never an `Array` or a `Hash`
+    Scalar(Yaml),
+    Seq(Vec<Node>),
+    Map(Vec<(Node, Node)>),
+}
+impl Node {
+    fn as_str(&self) -> Option<&str> {
+        match &self.value {
+            Value::Scalar(Yaml::String(s)) => Some(s),
+            _ => None,
+        }
+    }
+    fn is_null(&self) -> bool {
+        matches!(self.value, Value::Scalar(Yaml::Null))
+    }
+}
+
+// Builds the marked node tree out of parser events
+#[derive(Default)]
+struct Loader {
+    docs: Vec<Node>,
+    // Open sequences and mappings, mappings also keep their pending key
+    stack: Vec<(Node, Option<Node>, usize)>,
+    anchors: HashMap<usize, Node>,
+}
+impl Loader {
+    fn insert(&mut self, node: Node, anchor: usize) {
+        // valid anchor ids start from 1
+        if anchor > 0 {
+            self.anchors.insert(anchor, node.clone());
+        }
+        match self.stack.last_mut() {
+            None => self.docs.push(node),
+            Some((parent, key, _)) => match &mut parent.value {
+                Value::Seq(items) => items.push(node),
+                Value::Map(pairs) => match key.take() {
+                    Some(k) => pairs.push((k, node)),
+                    None => *key = Some(node),
+                },
+                Value::Scalar(_) => unreachable!("scalars are never pushed on the stack"),
+            },
+        }
+    }
+}
+impl MarkedEventReceiver for Loader {
+    fn on_event(&mut self, ev: Event, mark: Marker) {
+        let mark = Mark::from(mark);
+        match ev {
+            Event::SequenceStart(aid) => self.stack.push((
+                Node {
+                    value: Value::Seq(Vec::new()),
+                    mark,
+                },
+                None,
+                aid,
+            )),
+            Event::MappingStart(aid) => self.stack.push((
+                Node {
+                    value: Value::Map(Vec::new()),
+                    mark,
+                },
+                None,
+                aid,
+            )),
+            Event::SequenceEnd | Event::MappingEnd => {
+                let (node, _, aid) = self.stack.pop().expect("unbalanced yaml events");
+                self.insert(node, aid);
+            }
+            Event::Scalar(v, style, aid, _) => {
+                let value = if style == TScalarStyle::Plain {
+                    Yaml::from_str(&v)
+                } else {
+                    Yaml::String(v)
+                };
+                self.insert(
+                    Node {
+                        value: Value::Scalar(value),
+                        mark,
+                    },
+                    aid,
+                );
+            }
+            Event::Alias(id) => {
+                let node = self.anchors.get(&id).cloned().unwrap_or(Node {
+                    value: Value::Scalar(Yaml::BadValue),
+                    mark,
+                });
+                self.insert(node, 0);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Loads the first document of `source`, `None` if the source is empty
+pub(crate) fn load(source: &str) -> Result<Option<Node>, SchemaError> {
+    let mut loader = Loader::default();
+    Parser::new(source.chars())
+        .load(&mut loader, false)
+        .map_err(SchemaError::from)?;
+    Ok(loader.docs.into_iter().next())
+}
+
+/// What went wrong while reading the errors yaml file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SchemaErrorKind {
+    /// The file is not valid yaml
+    Syntax(String),
+    /// The top level of the file is not a mapping of names to entries
+    NotAMapping,
+    /// An entry name is not a string
+    InvalidKey,
+    /// An entry name is not a valid rust identifier
+    InvalidIdent,
+    /// An entry name is a rust keyword
+    Keyword,
+    /// Two entries end up with the same name, `line` and `col` point at the other one
+    DuplicateKey {
+        other: String,
+        line: usize,
+        col: usize,
+    },
+    /// `code` is not a positive integer
+    InvalidCode,
+    /// `exit code` is not between 1 and 255
+    InvalidExitCode,
+    /// Two entries have the same code, `line` and `col` point at the other one
+    DuplicateCode {
+        other: String,
+        line: usize,
+        col: usize,
+    },
+    /// `params` is not a list of distinct names
+    InvalidParams,
+    /// `fix action` is not a name or a list of distinct names
+    InvalidActions,
+    /// `severity` is not `fatal`, `error` or `warning`
+    InvalidSeverity,
+    /// `category` is not a name
+    InvalidCategory,
+    /// A `{placeholder}` is not listed in `params`
+    UnknownParam(String),
+    /// A name in `params` is not used by any `{placeholder}`
+    UnusedParam(String),
+    /// An entry is not a mapping
+    InvalidEntry,
+    /// An entry has a field that is not understood
+    UnknownField(String),
+    /// An entry has no `message`
+    MissingMessage,
+    /// The `message` of an entry is not a string or a mapping of locales to strings
+    InvalidMessage,
+    /// `fix instructions` is not a list or a mapping of locales to lists
+    InvalidInstructions,
+    /// A fix instruction is not a string or a list of instructions
+    InvalidStep,
+    /// A list of sub instructions does not follow an instruction
+    OrphanSubsteps,
+}
+impl fmt::Display for SchemaErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SchemaErrorKind::Syntax(info) => write!(f, "invalid yaml: {}", info),
+            SchemaErrorKind::NotAMapping => {
+                write!(f, "expected a mapping of error names to entries")
+            }
+            SchemaErrorKind::InvalidKey => write!(f, "error names must be strings"),
+            SchemaErrorKind::InvalidIdent => {
+                write!(f, "error names must be valid rust identifiers")
+            }
+            SchemaErrorKind::Keyword => {
+                write!(f, "error names can't be rust keywords")
+            }
+            SchemaErrorKind::DuplicateKey { other, line, col } => {
+                write!(f, "collides with `{}` defined at {}:{}", other, line, col)
+            }
+            SchemaErrorKind::InvalidCode => write!(f, "`code` must be a positive integer"),
+            SchemaErrorKind::InvalidExitCode => {
+                write!(f, "`exit code` must be between 1 and 255")
+            }
+            SchemaErrorKind::DuplicateCode { other, line, col } => write!(
+                f,
+                "has the same code as `{}` defined at {}:{}",
+                other, line, col
+            ),
+            SchemaErrorKind::InvalidParams => {
+                write!(f, "`params` must be a list of distinct names")
+            }
+            SchemaErrorKind::InvalidActions => {
+                write!(f, "`fix action` must be a name or a list of distinct names")
+            }
+            SchemaErrorKind::InvalidSeverity => {
+                let names = Severity::ALL.map(Severity::name);
+                write!(f, "`severity` must be one of {}", names.join(", "))
+            }
+            SchemaErrorKind::InvalidCategory => write!(f, "`category` must be a name"),
+            SchemaErrorKind::UnknownParam(name) => {
+                write!(f, "`{{{}}}` is not listed in `params`", name)
+            }
+            SchemaErrorKind::UnusedParam(name) => {
+                write!(f, "param `{}` is never used", name)
+            }
+            SchemaErrorKind::InvalidEntry => {
+                write!(
+                    f,
+                    "expected a mapping with `message` and `fix instructions`"
+                )
+            }
+            SchemaErrorKind::UnknownField(field) => write!(f, "unknown field `{}`", field),
+            SchemaErrorKind::MissingMessage => write!(f, "missing `message`"),
+            SchemaErrorKind::InvalidMessage => {
+                write!(
+                    f,
+                    "`message` must be a string or a mapping of locales to strings"
+                )
+            }
+            SchemaErrorKind::InvalidInstructions => {
+                write!(
+                    f,
+                    "`fix instructions` must be a list or a mapping of locales to lists"
+                )
+            }
+            SchemaErrorKind::InvalidStep => {
+                write!(
+                    f,
+                    "fix instructions must be strings or lists of instructions"
+                )
+            }
+            SchemaErrorKind::OrphanSubsteps => {
+                write!(f, "a list of sub instructions must follow an instruction")
+            }
+        }
+    }
+}
+
+/// An error in the errors yaml file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaError {
+    /// The name of the entry the error was found in
+    pub key: Option<String>,
+    /// 1 based line of the offending node
+    pub line: usize,
+    /// 1 based column of the offending node
+    pub col: usize,
+    pub kind: SchemaErrorKind,
+}
+impl SchemaError {
+    pub(crate) fn new(kind: SchemaErrorKind, key: Option<&str>, mark: Mark) -> Self {
+        SchemaError {
+            key: key.map(String::from),
+            line: mark.line,
+            col: mark.col,
+            kind,
+        }
+    }
+}
+impl From<ScanError> for SchemaError {
+    fn from(e: ScanError) -> Self {
+        let mark = Mark::from(*e.marker());
+        // The Display impl of ScanError appends the position again
+        let info = e.to_string();
+        let info = info.split(" at line ").next().unwrap_or_default();
+        SchemaError::new(SchemaErrorKind::Syntax(info.to_string()), None, mark)
+    }
+}
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: ", self.line, self.col)?;
+        if let Some(key) = &self.key {
+            write!(f, "in `{}`: ", key)?;
+        }
+        write!(f, "{}", self.kind)
+    }
+}
+impl std::error::Error for SchemaError {}
+
+/// An owned fix instruction with its sub instructions
+///
+/// Used for panics added to a `PanicRegistry` in code
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instruction {
+    pub text: String,
+    pub children: Vec<Instruction>,
+}
+impl Instruction {
+    pub fn new(text: impl Into<String>) -> Self {
+        Instruction {
+            text: text.into(),
+            children: Vec::new(),
+        }
+    }
+    /// Adds a sub instruction
+    pub fn child(mut self, child: impl Into<Instruction>) -> Self {
+        self.children.push(child.into());
+        self
+    }
+}
+impl From<&str> for Instruction {
+    fn from(text: &str) -> Self {
+        Instruction::new(text)
+    }
+}
+impl From<String> for Instruction {
+    fn from(text: String) -> Self {
+        Instruction::new(text)
+    }
+}
+
+/// How bad a panic is, set by `severity` in the yaml file
+///
+/// It picks the first line of the output and the level of the `LogSink` and
+/// `SyslogSink` messages, the hook still exits the same way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Severity {
+    /// The program can't go on
+    #[default]
+    Fatal,
+    /// The current operation failed
+    Error,
+    /// Something the user should know about
+    Warning,
+}
+impl Severity {
+    const ALL: [Severity; 3] = [Severity::Fatal, Severity::Error, Severity::Warning];
+
+    /// The name used in the yaml file and the JSON output
+    pub fn name(self) -> &'static str {
+        match self {
+            Severity::Fatal => "fatal",
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+}
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// One panic described in the yaml file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub key: String,
+    pub mark: Mark,
+    /// Numeric code of the entry, its position starting at 1 unless set in the yaml
+    pub code: u32,
+    /// Exit code of the process set in the yaml
+    pub exit_code: Option<i32>,
+    /// Names of the registered fix actions
+    pub fix_actions: Vec<String>,
+    pub severity: Severity,
+    pub category: Option<String>,
+    pub message: String,
+    pub fix_instructions: Option<Vec<Instruction>>,
+    /// Names of the `{placeholders}` filled in when the panic is raised,
+    /// `None` if the texts are not templates
+    pub params: Option<Vec<String>>,
+    /// The texts for every locale listed in the yaml file
+    pub translations: Vec<Translation>,
+}
+
+/// Message and instructions of an entry for one locale
+#[derive(Debug, Clone, PartialEq)]
+pub struct Translation {
+    pub locale: String,
+    pub message: String,
+    pub fix_instructions: Option<Vec<Instruction>>,
+}
+
+/// Reads every entry of the yaml source, collecting all the errors found
+pub fn parse(source: &str) -> Result<Vec<Entry>, Vec<SchemaError>> {
+    debug!("Started Reading the yaml string");
+    let root = match load(source).map_err(|e| vec![e])? {
+        Some(root) if !root.is_null() => root,
+        // An empty file simply has no entries
+        _ => return Ok(Vec::new()),
+    };
+    let pairs = match root.value {
+        Value::Map(pairs) => pairs,
+        _ => {
+            return Err(vec![SchemaError::new(
+                SchemaErrorKind::NotAMapping,
+                None,
+                root.mark,
+            )])
+        }
+    };
+    let mut entries = Vec::new();
+    let mut errors = Vec::new();
+    let mut seen = HashMap::new();
+    for (key, val) in &pairs {
+        if let Some(name) = key.as_str() {
+            // yaml_rust would silently keep only the last one
+            if let Some(first) = seen.insert(name, key.mark) {
+                errors.push(SchemaError::new(
+                    SchemaErrorKind::DuplicateKey {
+                        other: name.to_string(),
+                        line: first.line,
+                        col: first.col,
+                    },
+                    Some(name),
+                    key.mark,
+                ));
+                continue;
+            }
+        }
+        match parse_entry(key, val) {
+            Ok(entry) => entries.push(entry),
+            Err(mut e) => errors.append(&mut e),
+        }
+    }
+    // Entries without a code are numbered in order, skipping the pinned codes
+    let pinned: HashSet<u32> = entries.iter().map(|e| e.code).collect();
+    let mut next = 0;
+    for entry in entries.iter_mut().filter(|e| e.code == 0) {
+        next = (next + 1..)
+            .find(|c| !pinned.contains(c))
+            .unwrap_or_default();
+        entry.code = next;
+    }
+    let mut codes: HashMap<u32, &Entry> = HashMap::new();
+    for entry in &entries {
+        if let Some(first) = codes.insert(entry.code, entry) {
+            errors.push(SchemaError::new(
+                SchemaErrorKind::DuplicateCode {
+                    other: first.key.clone(),
+                    line: first.mark.line,
+                    col: first.mark.col,
+                },
+                Some(&entry.key),
+                entry.mark,
+            ));
+        }
+    }
+    if errors.is_empty() {
+        Ok(entries)
+    } else {
+        Err(errors)
+    }
+}
+
+fn parse_entry(key: &Node, val: &Node) -> Result<Entry, Vec<SchemaError>> {
+    let name = key.as_str().ok_or_else(|| {
+        vec![SchemaError::new(
+            SchemaErrorKind::InvalidKey,
+            None,
+            key.mark,
+        )]
+    })?;
+    debug!("parsing key {}", name);
+    let err = |kind, mark| SchemaError::new(kind, Some(name), mark);
+    let fields = match &val.value {
+        Value::Map(fields) => fields,
+        _ => return Err(vec![err(SchemaErrorKind::InvalidEntry, val.mark)]),
+    };
+    let mut errors = Vec::new();
+    // Texts per locale, the empty locale is used when they are not translated
+    let mut messages: Vec<(String, String)> = Vec::new();
+    let mut steps: Vec<(String, Option<Vec<Instruction>>)> = Vec::new();
+    let mut code = 0;
+    let mut exit_code = None;
+    let mut fix_actions = Vec::new();
+    let mut severity = Severity::default();
+    let mut category = None;
+    let mut params = None;
+    // Where to point at for problems with the params
+    let (mut message_mark, mut steps_mark, mut params_mark) = (key.mark, key.mark, key.mark);
+    for (field, value) in fields {
+        match field.as_str() {
+            Some("message") => {
+                message_mark = value.mark;
+                match per_locale(value, |v| v.as_str().map(String::from).ok_or(())) {
+                    Ok(m) => messages = m,
+                    Err(_) => errors.push(err(SchemaErrorKind::InvalidMessage, value.mark)),
+                }
+            }
+            Some("fix instructions") => {
+                steps_mark = value.mark;
+                match per_locale(value, parse_steps) {
+                    Ok(s) => steps = s,
+                    Err(Some((kind, mark))) => errors.push(err(kind, mark)),
+                    Err(None) => errors.push(err(SchemaErrorKind::InvalidInstructions, value.mark)),
+                }
+            }
+            Some("code") => match value.value {
+                Value::Scalar(Yaml::Integer(c)) if c > 0 && c <= u32::MAX as i64 => code = c as u32,
+                _ => errors.push(err(SchemaErrorKind::InvalidCode, value.mark)),
+            },
+            // Exit codes past 255 are truncated on unix
+            Some("exit code") => match value.value {
+                Value::Scalar(Yaml::Integer(c)) if (1..=255).contains(&c) => {
+                    exit_code = Some(c as i32)
+                }
+                _ => errors.push(err(SchemaErrorKind::InvalidExitCode, value.mark)),
+            },
+            Some("fix action") => match value.as_str() {
+                Some(name) => fix_actions = vec![name.to_string()],
+                None => match parse_params(value) {
+                    Some(names) => fix_actions = names,
+                    None => errors.push(err(SchemaErrorKind::InvalidActions, value.mark)),
+                },
+            },
+            Some("severity") => match Severity::ALL
+                .into_iter()
+                .find(|s| Some(s.name()) == value.as_str())
+            {
+                Some(s) => severity = s,
+                None => errors.push(err(SchemaErrorKind::InvalidSeverity, value.mark)),
+            },
+            Some("category") => match value.as_str() {
+                Some(name) if !name.trim().is_empty() => category = Some(name.to_string()),
+                _ => errors.push(err(SchemaErrorKind::InvalidCategory, value.mark)),
+            },
+            Some("params") => match parse_params(value) {
+                Some(names) => {
+                    params = Some(names);
+                    params_mark = value.mark;
+                }
+                None => errors.push(err(SchemaErrorKind::InvalidParams, value.mark)),
+            },
+            Some(other) => errors.push(err(
+                SchemaErrorKind::UnknownField(other.to_string()),
+                field.mark,
+            )),
+            None => errors.push(err(
+                SchemaErrorKind::UnknownField(format!("{:?}", field.value)),
+                field.mark,
+            )),
+        }
+    }
+    if messages.is_empty()
+        && !errors
+            .iter()
+            .any(|e| e.kind == SchemaErrorKind::InvalidMessage)
+    {
+        errors.push(err(SchemaErrorKind::MissingMessage, key.mark));
+    }
+    if let Some(params) = &params {
+        // Every placeholder has to be declared and every declared param used
+        let mut used = Vec::new();
+        let mut check = |text: &str, mark| {
+            for name in placeholders(text) {
+                if !params.iter().any(|p| p == name) {
+                    errors.push(err(SchemaErrorKind::UnknownParam(name.to_string()), mark));
+                }
+                used.push(name.to_string());
+            }
+        };
+        for (_, message) in &messages {
+            check(message, message_mark);
+        }
+        fn walk(steps: &[Instruction], check: &mut dyn FnMut(&str)) {
+            for step in steps {
+                check(&step.text);
+                walk(&step.children, check);
+            }
+        }
+        for (_, list) in &steps {
+            walk(list.as_deref().unwrap_or_default(), &mut |t| {
+                check(t, steps_mark)
+            });
+        }
+        for param in params {
+            if !used.contains(param) {
+                errors.push(err(
+                    SchemaErrorKind::UnusedParam(param.clone()),
+                    params_mark,
+                ));
+            }
+        }
+    }
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    let message = default_text(&messages).cloned().unwrap_or_default();
+    let fix_instructions = default_text(&steps).cloned().flatten();
+    // Every locale gets complete texts, falling back to the default ones
+    let mut translations: Vec<Translation> = Vec::new();
+    for locale in messages
+        .iter()
+        .map(|m| &m.0)
+        .chain(steps.iter().map(|s| &s.0))
+    {
+        if locale.is_empty() || translations.iter().any(|t| &t.locale == locale) {
+            continue;
+        }
+        translations.push(Translation {
+            locale: locale.clone(),
+            message: find_locale(&messages, locale).unwrap_or(&message).clone(),
+            fix_instructions: find_locale(&steps, locale)
+                .unwrap_or(&fix_instructions)
+                .clone(),
+        });
+    }
+    Ok(Entry {
+        key: name.to_string(),
+        mark: key.mark,
+        code,
+        exit_code,
+        fix_actions,
+        severity,
+        category,
+        message,
+        fix_instructions,
+        params,
+        translations,
+    })
+}
+
+// A value, or a mapping of locales to values
+fn per_locale<T, E>(
+    node: &Node,
+    parse: impl Fn(&Node) -> Result<T, E>,
+) -> Result<Vec<(String, T)>, Option<E>> {
+    match &node.value {
+        Value::Map(pairs) if !pairs.is_empty() => pairs
+            .iter()
+            .map(|(locale, value)| match locale.as_str() {
+                Some(locale) if !locale.is_empty() => {
+                    Ok((locale.to_string(), parse(value).map_err(Some)?))
+                }
+                _ => Err(None),
+            })
+            .collect(),
+        Value::Map(_) => Err(None),
+        _ => Ok(vec![(String::new(), parse(node).map_err(Some)?)]),
+    }
+}
+
+fn find_locale<'a, T>(texts: &'a [(String, T)], locale: &str) -> Option<&'a T> {
+    texts.iter().find(|t| t.0 == locale).map(|t| &t.1)
+}
+
+// The untranslated text, or the english one, or the first one listed
+fn default_text<T>(texts: &[(String, T)]) -> Option<&T> {
+    texts
+        .iter()
+        .find(|t| t.0.is_empty())
+        .or_else(|| texts.iter().find(|t| t.0 == "en"))
+        .or_else(|| texts.first())
+        .map(|t| &t.1)
+}
+
+// A list of distinct names
+fn parse_params(node: &Node) -> Option<Vec<String>> {
+    let mut names: Vec<String> = Vec::new();
+    match &node.value {
+        Value::Seq(items) => {
+            for item in items {
+                let name = item.as_str()?;
+                if names.iter().any(|n| n == name) {
+                    return None;
+                }
+                names.push(name.to_string());
+            }
+            Some(names)
+        }
+        _ => None,
+    }
+}
+
+/// The `{name}` placeholders in a text, anything else between braces is left alone
+pub(crate) fn placeholders(text: &str) -> Vec<&str> {
+    let mut names = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('{') {
+        rest = &rest[start + 1..];
+        let end = rest
+            .find(|c: char| !(c == '_' || c.is_ascii_alphanumeric()))
+            .unwrap_or(rest.len());
+        let name = &rest[..end];
+        let starts_ok = name.starts_with(|c: char| c == '_' || c.is_ascii_alphabetic());
+        if starts_ok && rest[end..].starts_with('}') {
+            names.push(name);
+        }
+    }
+    names
+}
+
+// Each instruction is a string that can be followed by a list of sub instructions,
+// which follow the same layout all the way down
+fn parse_steps(node: &Node) -> Result<Option<Vec<Instruction>>, (SchemaErrorKind, Mark)> {
+    match &node.value {
+        _ if node.is_null() => Ok(None),
+        Value::Seq(items) => {
+            debug!("Number of instuctions {}", items.len());
+            parse_step_list(items).map(Some)
+        }
+        _ => Err((SchemaErrorKind::InvalidInstructions, node.mark)),
+    }
+}
+
+fn parse_step_list(items: &[Node]) -> Result<Vec<Instruction>, (SchemaErrorKind, Mark)> {
+    let mut steps: Vec<Instruction> = Vec::new();
+    for item in items {
+        match (&item.value, item.as_str()) {
+            (_, Some(text)) => steps.push(Instruction {
+                text: text.to_string(),
+                children: Vec::new(),
+            }),
+            (Value::Seq(subs), _) => match steps.last_mut() {
+                Some(parent) if parent.children.is_empty() => {
+                    parent.children = parse_step_list(subs)?;
+                }
+                _ => return Err((SchemaErrorKind::OrphanSubsteps, item.mark)),
+            },
+            _ => return Err((SchemaErrorKind::InvalidStep, item.mark)),
+        }
+    }
+    Ok(steps)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn errors(s: &str) -> Vec<SchemaError> {
+        parse(s).unwrap_err()
+    }
+
+    #[test]
+    fn reports_position_and_key() {
+        let e = errors("foo:\n  message: fine\nbar:\n  mesage: typo\n");
+        assert_eq!(e.len(), 2);
+        assert_eq!(e[0].key.as_deref(), Some("bar"));
+        assert_eq!(e[0].kind, SchemaErrorKind::UnknownField("mesage".into()));
+        assert_eq!((e[0].line, e[0].col), (4, 3));
+        assert_eq!(e[1].kind, SchemaErrorKind::MissingMessage);
+        assert_eq!((e[1].line, e[1].col), (3, 1));
+        assert_eq!(e[0].to_string(), "4:3: in `bar`: unknown field `mesage`");
+    }
+
+    #[test]
+    fn invalid_values() {
+        let e = errors("404:\n  message: x\n");
+        assert_eq!(e[0].kind, SchemaErrorKind::InvalidKey);
+        let e = errors("foo:\n  message: [a, b]\n");
+        assert_eq!(e[0].kind, SchemaErrorKind::InvalidMessage);
+        assert_eq!(e.len(), 1);
+        let e = errors("foo:\n  message: x\n  fix instructions: nope\n");
+        assert_eq!(e[0].kind, SchemaErrorKind::InvalidInstructions);
+        let e = errors("foo:\n  message: x\n  fix instructions:\n    - - orphan\n");
+        assert_eq!(e[0].kind, SchemaErrorKind::OrphanSubsteps);
+        assert_eq!((e[0].line, e[0].col), (4, 7));
+        let e = errors("foo:\n  message: x\n  fix instructions:\n    - a: b\n");
+        assert_eq!(e[0].kind, SchemaErrorKind::InvalidStep);
+        let e = errors("- foo\n- bar\n");
+        assert_eq!(e[0].kind, SchemaErrorKind::NotAMapping);
+    }
+
+    #[test]
+    fn nested_steps() {
+        let entries = parse(
+            "foo:
+  message: x
+  fix instructions:
+    - one
+    - - one a
+      - - one a i
+        - one a ii
+        - - one a ii 1
+      - one b
+    - two
+",
+        )
+        .unwrap();
+        let step = |text: &str, children| Instruction {
+            text: text.into(),
+            children,
+        };
+        assert_eq!(
+            entries[0].fix_instructions,
+            Some(vec![
+                step(
+                    "one",
+                    vec![
+                        step(
+                            "one a",
+                            vec![
+                                step("one a i", vec![]),
+                                step("one a ii", vec![step("one a ii 1", vec![])]),
+                            ]
+                        ),
+                        step("one b", vec![]),
+                    ]
+                ),
+                step("two", vec![]),
+            ])
+        );
+        let e =
+            errors("foo:\n  message: x\n  fix instructions:\n    - a\n    - - b\n      - - - c\n");
+        assert_eq!(e[0].kind, SchemaErrorKind::OrphanSubsteps);
+        assert_eq!((e[0].line, e[0].col), (6, 11));
+    }
+
+    #[test]
+    fn syntax_error() {
+        let e = errors("foo:\n  message: \"unterminated\n");
+        assert!(matches!(e[0].kind, SchemaErrorKind::Syntax(_)));
+        assert!(e[0].key.is_none());
+    }
+
+    #[test]
+    fn duplicate_keys() {
+        let e = errors("foo:\n  message: a\nfoo:\n  message: b\n");
+        assert_eq!(e.len(), 1);
+        assert_eq!((e[0].line, e[0].col), (3, 1));
+        assert_eq!(
+            e[0].to_string(),
+            "3:1: in `foo`: collides with `foo` defined at 1:1"
+        );
+    }
+
+    #[test]
+    fn codes() {
+        let entries =
+            parse("a:\n  message: x\nb:\n  message: x\n  code: 42\nc:\n  message: x\n").unwrap();
+        let codes: Vec<_> = entries.iter().map(|e| e.code).collect();
+        assert_eq!(codes, vec![1, 42, 2]);
+        // Pinning a code an earlier entry would get moves that entry along
+        let entries =
+            parse("a:\n  message: x\nb:\n  message: x\n  code: 1\nc:\n  message: x\n").unwrap();
+        let codes: Vec<_> = entries.iter().map(|e| e.code).collect();
+        assert_eq!(codes, vec![2, 1, 3]);
+        let e = errors("a:\n  message: x\n  code: 5\nb:\n  message: x\n  code: 5\n");
+        assert_eq!(
+            e[0].to_string(),
+            "4:1: in `b`: has the same code as `a` defined at 1:1"
+        );
+        for code in ["0", "-1", "x", "4294967296"] {
+            let e = errors(&format!("a:\n  message: x\n  code: {}\n", code));
+            assert_eq!(e[0].kind, SchemaErrorKind::InvalidCode);
+        }
+    }
+
+    #[test]
+    fn exit_codes() {
+        let entries = parse("a:\n  message: x\n  exit code: 4\nb:\n  message: x\n").unwrap();
+        let codes: Vec<_> = entries.iter().map(|e| e.exit_code).collect();
+        assert_eq!(codes, vec![Some(4), None]);
+        for code in ["0", "256", "x"] {
+            let e = errors(&format!("a:\n  message: x\n  exit code: {}\n", code));
+            assert_eq!(e[0].kind, SchemaErrorKind::InvalidExitCode);
+        }
+    }
+
+    #[test]
+    fn fix_actions() {
+        let entries =
+            parse("a:\n  message: x\n  fix action: clear-cache\nb:\n  message: x\n  fix action: [one, two]\n")
+                .unwrap();
+        assert_eq!(entries[0].fix_actions, ["clear-cache"]);
+        assert_eq!(entries[1].fix_actions, ["one", "two"]);
+        for actions in ["[a, a]", "3", "{a: b}"] {
+            let e = errors(&format!("a:\n  message: x\n  fix action: {}\n", actions));
+            assert_eq!(e[0].kind, SchemaErrorKind::InvalidActions);
+        }
+    }
+
+    #[test]
+    fn severities_and_categories() {
+        let entries =
+            parse("a:\n  message: x\n  severity: warning\n  category: network\nb:\n  message: x\n")
+                .unwrap();
+        assert_eq!(
+            (entries[0].severity, entries[0].category.as_deref()),
+            (Severity::Warning, Some("network"))
+        );
+        assert_eq!(
+            (entries[1].severity, entries[1].category.as_deref()),
+            (Severity::Fatal, None)
+        );
+        for severity in ["Fatal", "info", "[error]"] {
+            let e = errors(&format!("a:\n  message: x\n  severity: {}\n", severity));
+            assert_eq!(e[0].kind, SchemaErrorKind::InvalidSeverity);
+        }
+        assert_eq!(
+            errors("a:\n  message: x\n  severity: info\n")[0].to_string(),
+            "3:13: in `a`: `severity` must be one of fatal, error, warning"
+        );
+        for category in ["''", "3", "[network]"] {
+            let e = errors(&format!("a:\n  message: x\n  category: {}\n", category));
+            assert_eq!(e[0].kind, SchemaErrorKind::InvalidCategory);
+        }
+    }
+
+    #[test]
+    fn params() {
+        let entries = parse(
+            "foo:
+  message: Could not open {path} {not a param} {0}
+  params: [path, url]
+  fix instructions:
+    - Check that {path} exists
+    - - Try opening {url} in a browser
+",
+        )
+        .unwrap();
+        assert_eq!(
+            entries[0].params,
+            Some(vec!["path".to_string(), "url".to_string()])
+        );
+        let e = errors("foo:\n  message: Could not open {path}\n  params: [file]\n");
+        assert_eq!(e[0].kind, SchemaErrorKind::UnknownParam("path".into()));
+        assert_eq!((e[0].line, e[0].col), (2, 12));
+        assert_eq!(e[1].kind, SchemaErrorKind::UnusedParam("file".into()));
+        assert_eq!(
+            e[1].to_string(),
+            "3:11: in `foo`: param `file` is never used"
+        );
+        let e = errors("foo:\n  message: x\n  params: [a, a]\n");
+        assert_eq!(e[0].kind, SchemaErrorKind::InvalidParams);
+        // Without params braces are plain text
+        assert!(parse("foo:\n  message: Could not open {path}\n").is_ok());
+    }
+
+    #[test]
+    fn translations() {
+        let entries = parse(
+            "foo:
+  message:
+    de: Die Datenbank ist kaputt
+    en: The database is broken
+    fr: La base de données est cassée
+  fix instructions:
+    de:
+      - Neu starten
+    en:
+      - Restart
+bar:
+  message: Plain
+  fix instructions:
+    de: [Neu starten]
+",
+        )
+        .unwrap();
+        let foo = &entries[0];
+        assert_eq!(foo.message, "The database is broken");
+        assert_eq!(
+            foo.fix_instructions,
+            Some(vec![Instruction::new("Restart")])
+        );
+        let locales: Vec<_> = foo.translations.iter().map(|t| t.locale.as_str()).collect();
+        assert_eq!(locales, vec!["de", "en", "fr"]);
+        assert_eq!(
+            foo.translations[0].fix_instructions,
+            Some(vec![Instruction::new("Neu starten")])
+        );
+        // Missing texts fall back to the default ones
+        assert_eq!(
+            foo.translations[2].fix_instructions,
+            Some(vec![Instruction::new("Restart")])
+        );
+        let bar = &entries[1];
+        assert_eq!(bar.message, "Plain");
+        assert_eq!(bar.translations[0].message, "Plain");
+        assert_eq!(
+            bar.fix_instructions,
+            Some(vec![Instruction::new("Neu starten")])
+        );
+
+        let e = errors("foo:\n  message:\n    de: [a]\n");
+        assert_eq!(e[0].kind, SchemaErrorKind::InvalidMessage);
+        let e = errors("foo:\n  message: {}\n");
+        assert_eq!(e[0].kind, SchemaErrorKind::InvalidMessage);
+        let e = errors("foo:\n  message: x\n  fix instructions:\n    de: nope\n");
+        assert_eq!(e[0].kind, SchemaErrorKind::InvalidInstructions);
+        let e = errors("foo:\n  message: {en: \"{a}\", de: \"{b}\"}\n  params: [a]\n");
+        assert_eq!(e[0].kind, SchemaErrorKind::UnknownParam("b".into()));
+    }
+
+    #[test]
+    fn empty_file() {
+        assert!(parse("").unwrap().is_empty());
+        assert!(parse("# only a comment\n").unwrap().is_empty());
+    }
+
+    #[test]
+    fn aliases_are_resolved() {
+        let entries = parse("foo:\n  message: &m shared\nbar:\n  message: *m\n").unwrap();
+        assert_eq!(entries[1].message, "shared");
+    }
+}
@@ -0,0 +1,138 @@
+//! Procedural macros for [user-panic](https://docs.rs/user-panic).
+//!
+//! Use them through the `macros` feature of `user-panic` instead of depending
+//! on this crate directly.
+
+use proc_macro::{Delimiter, Group, Ident, Literal, Punct, Spacing, Span, TokenStream, TokenTree};
+use std::path::PathBuf;
+use user_panic_codegen::codegen::{self, SetupOptions};
+
+/// Embeds the panics of a yaml file at compile time
+///
+/// Expands to the same consts and `PanicCode` enum that `panic_setup!` writes
+/// to `src/panic_structs.rs`, without a build script or files in the source tree.
+/// The path is relative to the `Cargo.toml` of the crate using the macro.
+/// Adding `normalize_names` after the path turns names like `api-error`
+/// into `API_ERROR`.
+///
+/// ```ignore
+/// user_panic::include_panics!("errors.yaml");
+/// user_panic::include_panics!("errors.yaml", normalize_names);
+/// ```
+///
+/// Mistakes in the yaml file are reported as compile errors pointing at the
+/// path with the line and column of the problem.
+#[proc_macro]
+pub fn include_panics(input: TokenStream) -> TokenStream {
+    let (path, span, options) = match parse_args(input) {
+        Ok(args) => args,
+        Err((msg, span)) => return compile_error(&msg, span),
+    };
+    let dir = std::env::var("CARGO_MANIFEST_DIR").unwrap_or_default();
+    let full_path = PathBuf::from(dir).join(&path);
+    let yaml = match std::fs::read_to_string(&full_path) {
+        Ok(yaml) => yaml,
+        Err(e) => {
+            let msg = format!("failed to read {}: {}", full_path.display(), e);
+            return compile_error(&msg, span);
+        }
+    };
+    let code = match codegen::generate(&yaml, &options) {
+        Ok(code) => code,
+        Err(errors) => {
+            return errors
+                .iter()
+                .map(|e| compile_error(&format!("{}:{}", path, e), span))
+                .collect()
+        }
+    };
+    // The private module keeps the imports from clashing with the caller's,
+    // include_bytes makes cargo rebuild when the yaml file changes
+    let module = format!(
+        "#[doc(hidden)]
+        #[allow(non_upper_case_globals)]
+        mod __user_panics {{
+            {}
+            const _: &[u8] = include_bytes!({:?});
+            {}
+        }}
+        pub use __user_panics::*;",
+        codegen::PRELUDE,
+        full_path.display().to_string(),
+        code
+    );
+    module.parse().expect("generated code is not valid rust")
+}
+
+fn parse_args(input: TokenStream) -> Result<(String, Span, SetupOptions), (String, Span)> {
+    let mut tokens = input.into_iter();
+    let (path, span) = match tokens.next() {
+        Some(TokenTree::Literal(lit)) => match string_value(&lit.to_string()) {
+            Some(path) => (path, lit.span()),
+            None => return Err(("expected a path string".into(), lit.span())),
+        },
+        // Literals passed through macro_rules are wrapped in an invisible group
+        Some(TokenTree::Group(g)) if g.delimiter() == Delimiter::None => {
+            return parse_args(g.stream().into_iter().chain(tokens).collect())
+        }
+        Some(other) => return Err(("expected a path string".into(), other.span())),
+        None => return Err(("expected a path string".into(), Span::call_site())),
+    };
+    let mut options = SetupOptions::default();
+    while let Some(token) = tokens.next() {
+        match token {
+            TokenTree::Punct(p) if p.as_char() == ',' => match tokens.next() {
+                Some(TokenTree::Ident(i)) if i.to_string() == "normalize_names" => {
+                    options.normalize_names = true;
+                }
+                Some(other) => return Err(("unknown option".into(), other.span())),
+                None => {}
+            },
+            other => return Err(("expected `,`".into(), other.span())),
+        }
+    }
+    Ok((path, span, options))
+}
+
+// The value of a string literal token, `None` for other literals
+fn string_value(lit: &str) -> Option<String> {
+    if let Some(raw) = lit.strip_prefix('r') {
+        let hashes = raw.len() - raw.trim_start_matches('#').len();
+        return raw
+            .get(hashes + 1..raw.len() - hashes - 1)
+            .map(String::from);
+    }
+    let inner = lit.strip_prefix('"')?.strip_suffix('"')?;
+    let mut s = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next()? {
+                'n' => s.push('\n'),
+                't' => s.push('\t'),
+                c => s.push(c),
+            },
+            c => s.push(c),
+        }
+    }
+    Some(s)
+}
+
+fn compile_error(msg: &str, span: Span) -> TokenStream {
+    let mut lit = Literal::string(msg);
+    lit.set_span(span);
+    let mut bang = Punct::new('!', Spacing::Alone);
+    bang.set_span(span);
+    let mut group = Group::new(Delimiter::Parenthesis, TokenTree::Literal(lit).into());
+    group.set_span(span);
+    let mut semi = Punct::new(';', Spacing::Alone);
+    semi.set_span(span);
+    [
+        TokenTree::Ident(Ident::new("compile_error", span)),
+        TokenTree::Punct(bang),
+        TokenTree::Group(group),
+        TokenTree::Punct(semi),
+    ]
+    .into_iter()
+    .collect()
+}
@@ -0,0 +1,64 @@
+use user_panic::UserPanic;
+use user_panic_macros::include_panics;
+
+mod panics {
+    user_panic_macros::include_panics!("tests/errors.yaml", normalize_names);
+}
+
+mod raw_path {
+    super::include_panics!(r"tests/errors.yaml", normalize_names);
+}
+
+#[test]
+fn consts_are_embedded() {
+    assert_eq!(
+        panics::API.error_msg,
+        "There was an error during the API request"
+    );
+    let steps = panics::API.fix_instructions.unwrap();
+    assert_eq!(steps[1].children[1].children[0].text, "API quota");
+    assert_eq!(panics::API.fix_actions, ["reset-quota"]);
+    assert_eq!(panics::API.severity, user_panic::Severity::Error);
+    assert_eq!(panics::API.category, Some("network"));
+    assert_eq!(panics::DB_DOWN.severity, user_panic::Severity::Fatal);
+    assert_eq!(
+        panics::DB_DOWN.error_msg,
+        "The \"database\" at C:\\db is {down}\n"
+    );
+    assert_eq!(raw_path::DB_DOWN.code, 10);
+    assert_eq!(raw_path::DB_DOWN.exit_code(), 69);
+}
+
+#[test]
+fn panic_code_is_embedded() {
+    use panics::PanicCode;
+    assert_eq!(
+        PanicCode::all(),
+        &[PanicCode::Api, PanicCode::DbDown, PanicCode::ConfigMissing]
+    );
+    assert_eq!("db-down".parse::<PanicCode>(), Ok(PanicCode::DbDown));
+    let panic: UserPanic = PanicCode::DbDown.into();
+    assert_eq!(PanicCode::of(&panic), Some(PanicCode::DbDown));
+}
+
+#[test]
+fn templates_are_constructors() {
+    let panic = panics::CONFIG_MISSING("app.toml");
+    assert_eq!(panic.args, vec![("path", "app.toml".to_string())]);
+    let s = panic.to_string();
+    assert!(s.contains("Error: Could not open app.toml\n"), "{}", s);
+    assert!(s.contains("1: Create app.toml\n"), "{}", s);
+}
+
+#[test]
+fn translations_are_embedded() {
+    let panic = panics::CONFIG_MISSING("app.toml");
+    assert_eq!(panic.translations.len(), 2);
+    let s = panic.display_in("de_DE.UTF-8");
+    assert!(
+        s.contains(": app.toml konnte nicht geöffnet werden\n"),
+        "{}",
+        s
+    );
+    assert!(s.contains("1: Create app.toml\n"), "{}", s);
+}